@@ -1,8 +1,7 @@
-use lazy_static::lazy_static;
 use std::{env, error, fmt::Display, result};
 use url::{self, Url};
 
-use crate::query::Query;
+use crate::{fetch::FetchError, query::Query};
 
 pub type Result<T> = result::Result<T, Error>;
 
@@ -114,9 +113,30 @@ impl From<Error> for Response {
     }
 }
 
+impl From<FetchError> for Error {
+    fn from(err: FetchError) -> Self {
+        let message = err.to_string();
+        let response = match &err {
+            FetchError::Unauthorized | FetchError::Forbidden => Response::perm_error(message),
+            FetchError::NotFound => Response::new(ResponseCode::NotFound, message),
+            FetchError::RateLimited(limited) => {
+                Response::slow_down(limited.retry_after.as_secs().max(1) as i32)
+            }
+            FetchError::Network(_) | FetchError::Decode(_) | FetchError::Upstream(_) => {
+                Response::proxy_error(message)
+            }
+            FetchError::Storage(_) => return Self::from_err(err),
+        };
+        Self::from_err_with_response(err, response)
+    }
+}
+
 impl From<Box<dyn error::Error>> for Error {
     fn from(err: Box<dyn error::Error>) -> Self {
-        Self::nested(err)
+        match err.downcast::<FetchError>() {
+            Ok(err) => (*err).into(),
+            Err(err) => Self::nested(err),
+        }
     }
 }
 
@@ -303,41 +323,142 @@ impl Display for Response {
     }
 }
 
-pub fn get_url() -> &'static str {
-    lazy_static! {
-        static ref GEMINI_URL: String = env::var("GEMINI_URL").unwrap();
+/// Strip the trailing slash so that `/foo/` and `/foo` reach the same route
+fn normalize_path(path: &str) -> String {
+    match path.trim_end_matches('/') {
+        "" => "/".into(),
+        path => path.into(),
     }
-    &GEMINI_URL
 }
 
-pub fn parse_url() -> Result<Url> {
-    Url::parse(get_url()).ok_else_response(|| Response::bad_request())
+/// Information about the client certificate that came with a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCert {
+    /// The fingerprint of the certificate
+    pub hash: String,
+    /// The common name of the certificate if the server provided it
+    pub name: Option<String>,
 }
 
-pub fn get_query() -> &'static str {
-    lazy_static! {
-        static ref QUERY_STRING: String = env::var("QUERY_STRING").unwrap();
+/// Everything a route needs to know about the request it is handling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub url: String,
+    pub path: String,
+    pub query: String,
+    pub script: String,
+    pub cert: Option<ClientCert>,
+}
+
+impl Request {
+    /// Create a request for a url where the capsule is mounted at `script`
+    pub fn new(url: &Url, script: impl Into<String>) -> Self {
+        let script = script.into();
+        let path = url.path().strip_prefix(&script).unwrap_or(url.path());
+        Self {
+            url: url.to_string(),
+            path: normalize_path(path),
+            query: url.query().unwrap_or("").into(),
+            script,
+            cert: None,
+        }
     }
 
-    &QUERY_STRING
-}
+    /// Load the request from the cgi environment variables
+    pub fn from_env() -> Result<Self> {
+        let url = env::var("GEMINI_URL")
+            .map_err(|_| Error::message("Missing GEMINI_URL environment variable"))?;
+        let path = env::var("PATH_INFO").unwrap_or_default();
+        let cert = env::var("TLS_CLIENT_HASH").ok().map(|hash| ClientCert {
+            hash,
+            name: env::var("REMOTE_USER").ok(),
+        });
 
-pub fn parse_query() -> Result<Query> {
-    Query::parse(get_query()).ok_else_response(|| Response::bad_request())
-}
+        Ok(Self {
+            url,
+            path: normalize_path(&path),
+            query: env::var("QUERY_STRING").unwrap_or_default(),
+            script: env::var("SCRIPT_NAME").unwrap_or_default(),
+            cert,
+        })
+    }
 
-pub fn get_path() -> &'static str {
-    lazy_static! {
-        static ref PATH_INFO: String = env::var("PATH_INFO").unwrap();
+    #[inline]
+    pub fn with_cert(mut self, cert: Option<ClientCert>) -> Self {
+        self.cert = cert;
+        self
     }
 
-    &PATH_INFO
-}
+    pub fn parse_url(&self) -> Result<Url> {
+        Url::parse(&self.url).ok_else_response(Response::bad_request)
+    }
+
+    pub fn parse_query(&self) -> Result<Query> {
+        Query::parse(&self.query).ok_else_response(Response::bad_request)
+    }
+
+    #[inline]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
 
-pub fn get_script() -> &'static str {
-    lazy_static! {
-        static ref SCRIPT_NAME: String = env::var("SCRIPT_NAME").unwrap();
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    #[inline]
+    pub fn script(&self) -> &str {
+        &self.script
     }
+}
 
-    &SCRIPT_NAME
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_from_url() {
+        let url = Url::parse("gemini://localhost/aoc/leaderboard/abc/2022/?s=time").unwrap();
+        let request = Request::new(&url, "/aoc");
+
+        assert_eq!(request.script(), "/aoc");
+        assert_eq!(request.path(), "/leaderboard/abc/2022");
+        assert_eq!(request.query(), "s=time");
+        assert_eq!(
+            request.parse_query().unwrap().get_value("s").unwrap(),
+            "time"
+        );
+    }
+
+    #[test]
+    fn test_fetch_error_response() {
+        let status = |err: FetchError| Response::from(Error::from(err)).status_code();
+
+        assert_eq!(status(FetchError::Unauthorized), 50);
+        assert_eq!(status(FetchError::NotFound), 51);
+        assert_eq!(
+            status(FetchError::Upstream(reqwest::StatusCode::BAD_REQUEST)),
+            43
+        );
+        assert_eq!(
+            status(FetchError::Storage(Box::new(std::io::Error::other(
+                "disk full"
+            )))),
+            42
+        );
+
+        // Boxed fetch errors keep their response
+        let boxed: Box<dyn error::Error> = Box::new(FetchError::NotFound);
+        assert_eq!(Response::from(Error::from(boxed)).status_code(), 51);
+    }
+
+    #[test]
+    fn test_request_root() {
+        let url = Url::parse("gemini://localhost").unwrap();
+        let request = Request::new(&url, "");
+
+        assert_eq!(request.path(), "/");
+        assert_eq!(request.query(), "");
+    }
 }
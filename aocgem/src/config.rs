@@ -0,0 +1,324 @@
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+    sync::OnceLock,
+    time::Duration,
+};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime};
+use serde::{de, Deserialize, Deserializer};
+
+/// How long leaderboards are cached for before they are fetched again
+///
+/// All times are in seconds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CachePolicy {
+    /// Cache time shortly after a puzzle unlocks
+    pub unlock: u64,
+    /// How long after midnight EST counts as shortly after the unlock
+    pub unlock_window: u64,
+    /// Cache time during the rest of the event (Dec 1-25)
+    pub active: u64,
+    /// Cache time outside of the event
+    pub inactive: u64,
+    /// Days after the event ends before a year is considered finished
+    ///
+    /// Finished years are never fetched again once they are cached. Set
+    /// this to -1 in the config to always keep refreshing old years.
+    #[serde(deserialize_with = "finished_after")]
+    pub finished_after: Option<u32>,
+}
+
+/// A negative number of days means years are never finished
+fn finished_after<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let value = i64::deserialize(deserializer)?;
+    if value < 0 {
+        return Ok(None);
+    }
+    u32::try_from(value)
+        .map(Some)
+        .map_err(|_| de::Error::custom(format!("finished_after is too large: {value}")))
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            unlock: 60,
+            unlock_window: 3600,
+            active: 900,
+            inactive: 3600,
+            finished_after: Some(30),
+        }
+    }
+}
+
+impl CachePolicy {
+    /// Get the max age of a cached leaderboard for a year
+    ///
+    /// `None` means the year is finished and the cache never expires.
+    pub fn max_age(&self, year: i32, now: DateTime<FixedOffset>) -> Option<Duration> {
+        let today = now.date_naive();
+        let start = NaiveDate::from_ymd_opt(year, 12, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(year, 12, 25).unwrap();
+
+        if let Some(days) = self.finished_after {
+            if today > end + chrono::Duration::days(days as i64) {
+                return None;
+            }
+        }
+
+        let seconds = if today >= start && today <= end {
+            // The competition is active
+            let since_midnight = now.time() - NaiveTime::from_hms_opt(0, 0, 0).unwrap();
+            if since_midnight.num_seconds() <= self.unlock_window as i64 {
+                // The competition has recently started
+                self.unlock
+            } else {
+                self.active
+            }
+        } else {
+            self.inactive
+        };
+        Some(Duration::from_secs(seconds))
+    }
+}
+
+/// Limits on requests to adventofcode.com, shared by every process
+///
+/// Rates are in requests per minute, and bursts are how many requests can be
+/// made at once.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    #[serde(deserialize_with = "rate")]
+    pub global_rate: f64,
+    #[serde(deserialize_with = "burst")]
+    pub global_burst: f64,
+    #[serde(deserialize_with = "rate")]
+    pub session_rate: f64,
+    #[serde(deserialize_with = "burst")]
+    pub session_burst: f64,
+}
+
+/// A rate has to be positive, or no request could ever be made
+fn rate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(de::Error::custom(format!(
+            "the rate must be a positive number, not {value}"
+        )))
+    }
+}
+
+/// A burst has to fit at least one request
+fn burst<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value >= 1.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(de::Error::custom(format!(
+            "the burst must be at least 1, not {value}"
+        )))
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            global_rate: 20.0,
+            global_burst: 10.0,
+            session_rate: 6.0,
+            session_burst: 3.0,
+        }
+    }
+}
+
+/// Where leaderboards are fetched from, and how this capsule identifies itself
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AocConfig {
+    pub base_url: String,
+    pub user_agent: String,
+    /// Someone to contact if this capsule causes problems for adventofcode.com
+    pub contact: Option<String>,
+}
+
+impl Default for AocConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://adventofcode.com".into(),
+            user_agent: concat!(
+                "aocgem/",
+                env!("CARGO_PKG_VERSION"),
+                " (github.com/ttocsneb/AoC22)"
+            )
+            .into(),
+            contact: None,
+        }
+    }
+}
+
+impl AocConfig {
+    pub fn get_user_agent(&self) -> String {
+        match &self.contact {
+            Some(contact) => format!("{} by {contact}", self.user_agent),
+            None => self.user_agent.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Where all of the data is stored
+    pub data_dir: PathBuf,
+    pub cache: CachePolicy,
+    pub rate_limit: RateLimit,
+    pub aoc: AocConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from(option_env!("DATA_DIR").unwrap_or("data")),
+            cache: CachePolicy::default(),
+            rate_limit: RateLimit::default(),
+            aoc: AocConfig::default(),
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Load the config from `AOCGEM_CONFIG`, or `aocgem.toml` if it exists
+pub fn load_config() -> Result<Config, Box<dyn Error>> {
+    let path = match env::var("AOCGEM_CONFIG") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => {
+            let path = PathBuf::from("aocgem.toml");
+            if !path.exists() {
+                return Ok(Config::default());
+            }
+            path
+        }
+    };
+
+    let mut f = File::open(&path)?;
+    let mut contents = String::new();
+    f.read_to_string(&mut contents)?;
+    toml::from_str(&contents).map_err(|err| {
+        Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid config {}: {err}", path.display()),
+        )) as Box<dyn Error>
+    })
+}
+
+/// Use `config` for the rest of the process
+pub fn set_config(config: Config) -> Result<(), Box<dyn Error>> {
+    CONFIG.set(config).map_err(|_| {
+        Box::new(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "The config has already been set",
+        )) as Box<dyn Error>
+    })
+}
+
+/// Get the config, falling back to the defaults if none was set
+pub fn get_config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::leaderboard::est_offset;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> DateTime<FixedOffset> {
+        est_offset()
+            .with_ymd_and_hms(year, month, day, hour, 30, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_tiers() {
+        let policy = CachePolicy::default();
+
+        let unlock = policy.max_age(2022, at(2022, 12, 5, 0)).unwrap();
+        assert_eq!(unlock.as_secs(), 60);
+        let active = policy.max_age(2022, at(2022, 12, 5, 10)).unwrap();
+        assert_eq!(active.as_secs(), 900);
+        let inactive = policy.max_age(2022, at(2022, 11, 20, 0)).unwrap();
+        assert_eq!(inactive.as_secs(), 3600);
+        let after = policy.max_age(2022, at(2023, 1, 10, 0)).unwrap();
+        assert_eq!(after.as_secs(), 3600);
+    }
+
+    #[test]
+    fn test_finished() {
+        let policy = CachePolicy::default();
+        assert!(policy.max_age(2021, at(2022, 12, 5, 0)).is_none());
+        assert!(policy.max_age(2022, at(2023, 1, 30, 0)).is_none());
+
+        let policy = CachePolicy {
+            finished_after: None,
+            ..Default::default()
+        };
+        assert!(policy.max_age(2021, at(2022, 12, 5, 0)).is_some());
+    }
+
+    #[test]
+    fn test_parse_finished() {
+        let config: Config = toml::from_str("[cache]\nactive = 600").unwrap();
+        assert_eq!(config.cache.finished_after, Some(30));
+        let config: Config = toml::from_str("[cache]\nfinished_after = 7").unwrap();
+        assert_eq!(config.cache.finished_after, Some(7));
+        let config: Config = toml::from_str("[cache]\nfinished_after = -1").unwrap();
+        assert_eq!(config.cache.finished_after, None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let config: Config = toml::from_str(
+            r#"
+data_dir = "/var/lib/aocgem"
+
+[cache]
+active = 600
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.data_dir, PathBuf::from("/var/lib/aocgem"));
+        assert_eq!(config.cache.active, 600);
+        assert_eq!(config.cache.unlock, 60);
+    }
+
+    #[test]
+    fn test_example() {
+        let config: Config = toml::from_str(include_str!("../aocgem.example.toml")).unwrap();
+
+        assert_eq!(config.cache.finished_after, Some(30));
+        assert_eq!(config.rate_limit.global_rate, 20.0);
+    }
+
+    #[test]
+    fn test_invalid_rate_limit() {
+        let config: Config = toml::from_str("[rate_limit]\nsession_rate = 0.5").unwrap();
+        assert_eq!(config.rate_limit.session_rate, 0.5);
+        assert_eq!(config.rate_limit.session_burst, 3.0);
+
+        assert!(toml::from_str::<Config>("[rate_limit]\nglobal_rate = 0.0").is_err());
+        assert!(toml::from_str::<Config>("[rate_limit]\nsession_rate = -1.0").is_err());
+        assert!(toml::from_str::<Config>("[rate_limit]\nglobal_burst = 0.0").is_err());
+        assert!(toml::from_str::<Config>("[rate_limit]\nsession_burst = 0.5").is_err());
+    }
+}
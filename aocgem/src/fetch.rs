@@ -1,65 +1,273 @@
 use std::{
     error::Error,
-    fs::{self, File},
-    io::{self, Read, Write},
+    fmt::Display,
+    io,
     path::PathBuf,
     time::{Duration, SystemTime},
 };
 
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use regex::Regex;
 use reqwest::{
     blocking::Client,
-    header::{HeaderMap, HeaderValue},
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE, LOCATION, RETRY_AFTER},
+    redirect::Policy,
+    StatusCode,
 };
 
-use crate::leaderboard::{est_offset, Leaderboard, PublicLeaderboard};
+use crate::{
+    config::{get_config, AocConfig},
+    leaderboard::{board_ids, est_offset, Identity, Leaderboard, PublicLeaderboard},
+    lock::FileLock,
+    misses::{Miss, Misses},
+    ratelimit::{RateLimited, RateLimiter},
+    secret::{decrypt, encrypt, is_encrypted, load_key},
+    store::{get_store, Store},
+};
 
-pub fn fetch_leaderboard(
-    session: &str,
-    group: &str,
-    year: i32,
-) -> Result<Leaderboard, Box<dyn Error>> {
-    let url = format!("https://adventofcode.com/{year}/leaderboard/private/view/{group}.json");
+/// Why a leaderboard could not be fetched
+#[derive(Debug)]
+pub enum FetchError {
+    /// The session is invalid or has expired
+    Unauthorized,
+    /// The session is valid, but isn't a member of the leaderboard
+    Forbidden,
+    /// The leaderboard doesn't exist for the year
+    NotFound,
+    RateLimited(RateLimited),
+    /// adventofcode.com could not be reached, or had an error of its own
+    Network(reqwest::Error),
+    /// The response was not a leaderboard
+    Decode(reqwest::Error),
+    /// adventofcode.com answered with a status that doesn't mean any of the above
+    Upstream(StatusCode),
+    /// The cache could not be read or written
+    Storage(Box<dyn Error>),
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Unauthorized => f.write_str("The session is invalid or has expired"),
+            FetchError::Forbidden => {
+                f.write_str("The session does not have access to the leaderboard")
+            }
+            FetchError::NotFound => f.write_str("The leaderboard does not exist for that year"),
+            FetchError::RateLimited(limited) => limited.fmt(f),
+            FetchError::Network(err) => match err.status() {
+                Some(status) => f.write_fmt(format_args!(
+                    "adventofcode.com is having problems ({status}), try again later"
+                )),
+                None => f.write_str("Could not reach adventofcode.com"),
+            },
+            FetchError::Decode(_) => f.write_str("adventofcode.com did not send a leaderboard"),
+            FetchError::Upstream(status) => f.write_fmt(format_args!(
+                "adventofcode.com answered with an unexpected status ({status})"
+            )),
+            FetchError::Storage(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for FetchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FetchError::RateLimited(err) => Some(err),
+            FetchError::Network(err) => Some(err),
+            FetchError::Decode(err) => Some(err),
+            FetchError::Storage(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<Box<dyn Error>> for FetchError {
+    fn from(err: Box<dyn Error>) -> Self {
+        match err.downcast::<RateLimited>() {
+            Ok(limited) => FetchError::RateLimited(*limited),
+            Err(err) => FetchError::Storage(err),
+        }
+    }
+}
 
+/// How long adventofcode.com asked us to wait, in seconds or as a date
+fn retry_after(headers: &HeaderMap) -> Duration {
+    let value = headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Duration::from_secs(seconds);
+    }
+    match DateTime::parse_from_rfc2822(value) {
+        Ok(date) => (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+        // Wait a minute when it doesn't say
+        Err(_) => Duration::from_secs(60),
+    }
+}
+
+/// A client that sends requests to adventofcode.com as the session
+fn session_client(aoc: &AocConfig, session: &str) -> Result<Client, FetchError> {
     let mut headers = HeaderMap::new();
     headers.insert(
         "cookie",
-        HeaderValue::from_str(&format!("session={session}"))?,
+        HeaderValue::from_str(&format!("session={session}"))
+            .map_err(|_| FetchError::Unauthorized)?,
     );
-    let client = Client::builder().default_headers(headers).build()?;
-    let request = client.get(url).build()?;
-    let response = client.execute(request)?;
-
-    if response.status().is_success() {
-        match response.json() {
-            Ok(val) => Ok(val),
-            Err(_) => Err(Box::new(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Invalid leaderboard id",
-            ))),
-        }
-    } else if response.status().is_client_error() {
-        Err(Box::new(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Invalid year or leaderboard id",
-        )))
-    } else {
-        Err(Box::new(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Invalid Session",
-        )))
+    Client::builder()
+        .default_headers(headers)
+        .user_agent(aoc.get_user_agent())
+        .redirect(Policy::none())
+        .build()
+        .map_err(FetchError::Network)
+}
+
+/// Request the leaderboard from the AoC server described by `aoc`
+fn request_leaderboard(
+    aoc: &AocConfig,
+    session: &str,
+    group: &str,
+    year: i32,
+) -> Result<Leaderboard, FetchError> {
+    let base_url = aoc.base_url.trim_end_matches('/');
+    let url = format!("{base_url}/{year}/leaderboard/private/view/{group}.json");
+
+    let client = session_client(aoc, session)?;
+    let response = client.get(url).send().map_err(FetchError::Network)?;
+
+    let status = response.status();
+    if status.is_redirection() {
+        // adventofcode.com sends expired sessions to log in, and everyone
+        // else that can't see the leaderboard back to their own leaderboards
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .unwrap_or_default();
+        return Err(if location.contains("/auth/") {
+            FetchError::Unauthorized
+        } else {
+            FetchError::Forbidden
+        });
     }
+    match status {
+        StatusCode::UNAUTHORIZED => return Err(FetchError::Unauthorized),
+        StatusCode::FORBIDDEN => return Err(FetchError::Forbidden),
+        StatusCode::NOT_FOUND => return Err(FetchError::NotFound),
+        StatusCode::TOO_MANY_REQUESTS => {
+            return Err(FetchError::RateLimited(RateLimited {
+                retry_after: retry_after(response.headers()),
+            }))
+        }
+        _ if status.is_server_error() => {
+            return Err(FetchError::Network(
+                response.error_for_status().unwrap_err(),
+            ))
+        }
+        _ if !status.is_success() => return Err(FetchError::Upstream(status)),
+        _ => {}
+    }
+
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|mime| mime.to_str().ok())
+        .is_some_and(|mime| mime.starts_with("application/json"));
+    if !is_json {
+        // An html page instead of the leaderboard is the login page
+        return Err(FetchError::Unauthorized);
+    }
+    response.json().map_err(FetchError::Decode)
 }
 
-#[inline]
-fn get_cache_path() -> PathBuf {
-    PathBuf::from(option_env!("DATA_DIR").unwrap_or("data"))
+/// Request the user id of the session from its settings page
+///
+/// The settings page offers to show the user as `(anonymous user #123)`, which
+/// is the id members have on leaderboards.
+fn request_member_id(aoc: &AocConfig, session: &str) -> Result<i32, FetchError> {
+    let base_url = aoc.base_url.trim_end_matches('/');
+    let client = session_client(aoc, session)?;
+    let response = client
+        .get(format!("{base_url}/settings"))
+        .send()
+        .map_err(FetchError::Network)?;
+
+    let status = response.status();
+    match status {
+        // Expired sessions are sent to log in
+        _ if status.is_redirection() => return Err(FetchError::Unauthorized),
+        StatusCode::UNAUTHORIZED => return Err(FetchError::Unauthorized),
+        StatusCode::TOO_MANY_REQUESTS => {
+            return Err(FetchError::RateLimited(RateLimited {
+                retry_after: retry_after(response.headers()),
+            }))
+        }
+        _ if status.is_server_error() => {
+            return Err(FetchError::Network(
+                response.error_for_status().unwrap_err(),
+            ))
+        }
+        _ if !status.is_success() => return Err(FetchError::Upstream(status)),
+        _ => {}
+    }
+
+    let page = response.text().map_err(FetchError::Decode)?;
+    let regex = Regex::new(r"anonymous user #(\d+)").unwrap();
+    regex
+        .captures(&page)
+        .and_then(|captures| captures[1].parse().ok())
+        .ok_or(FetchError::Unauthorized)
+}
+
+/// Find out which leaderboard member the session belongs to
+pub fn fetch_member_id(session: &str) -> Result<i32, FetchError> {
+    let config = get_config();
+    let limiter = RateLimiter::new(get_store().lock_dir(), config.rate_limit.clone());
+    limiter.acquire(session)?;
+    let result = request_member_id(&config.aoc, session);
+    if let Err(FetchError::RateLimited(limited)) = &result {
+        limiter.pause(limited.retry_after)?;
+    }
+    result
+}
+
+/// Fetch the leaderboard from adventofcode.com, every part of a meta board is fetched
+pub fn fetch_leaderboard(session: &str, group: &str, year: i32) -> Result<Leaderboard, FetchError> {
+    let ids = board_ids(group);
+    if ids.len() > 1 {
+        let leaderboards = ids
+            .into_iter()
+            .map(|id| fetch_leaderboard(session, id, year))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Leaderboard::merge(leaderboards).ok_or(FetchError::NotFound);
+    }
+
+    let config = get_config();
+    let limiter = RateLimiter::new(get_store().lock_dir(), config.rate_limit.clone());
+    limiter.acquire(session)?;
+    let result = request_leaderboard(&config.aoc, session, group, year);
+    if let Err(FetchError::RateLimited(limited)) = &result {
+        limiter.pause(limited.retry_after)?;
+    }
+    result
+}
+
+/// Fetch every part of the leaderboard with the session, and cache them for the next view
+pub fn renew_leaderboard(session: &str, group: &str, year: i32) -> Result<(), FetchError> {
+    for id in board_ids(group) {
+        let leaderboard = fetch_leaderboard(session, id, year)?;
+        save_leaderboard(&leaderboard, id, year)?;
+    }
+    Ok(())
 }
 
-fn get_data_path(group: &str, year: i32) -> PathBuf {
-    let mut path = get_cache_path();
-    path.push(format!("{group}-{year}.json"));
-    return path;
+#[inline]
+pub fn get_cache_path() -> PathBuf {
+    get_config().data_dir.clone()
 }
 
 pub fn save_leaderboard(
@@ -67,136 +275,579 @@ pub fn save_leaderboard(
     group: &str,
     year: i32,
 ) -> Result<(), Box<dyn Error>> {
-    let path = get_data_path(group, year);
-    let parent = path.parent().unwrap();
-    if !parent.exists() {
-        fs::create_dir_all(path.parent().unwrap())?;
-    }
-    let mut f = File::create(path)?;
-    let val = serde_json::to_string(&leaderboard)?;
-    f.write_all(val.as_bytes())?;
+    let store = get_store();
+    store.save_leaderboard(leaderboard, group, year)?;
+    let now = DateTime::<Utc>::from(SystemTime::now()).timestamp();
+    snapshot_leaderboard(store, leaderboard, group, year, now)
+}
+
+/// Save a snapshot of the leaderboard if it changed since the last one
+fn snapshot_leaderboard(
+    store: &dyn Store,
+    leaderboard: &Leaderboard,
+    group: &str,
+    year: i32,
+    at: i64,
+) -> Result<(), Box<dyn Error>> {
+    let latest = match store.snapshots(group, year)?.last() {
+        Some(latest) => store.load_snapshot(group, year, *latest)?,
+        None => None,
+    };
+    // Compare the json values, since the order of members isn't stable
+    let changed = match latest {
+        Some(latest) => serde_json::to_value(latest)? != serde_json::to_value(leaderboard)?,
+        None => true,
+    };
+    if changed {
+        store.save_snapshot(leaderboard, group, year, at)?;
+    }
     Ok(())
 }
 
-pub fn load_leaderboard(group: &str, year: i32) -> Result<Leaderboard, Box<dyn Error>> {
-    let path = get_data_path(group, year);
-    let mut f = File::open(path)?;
-    let mut contents = String::new();
-    f.read_to_string(&mut contents)?;
-    Ok(serde_json::from_str(&contents)?)
+/// A leaderboard as it was at some point in time
+pub struct Snapshot {
+    pub leaderboard: Leaderboard,
+    /// When the snapshot was taken
+    pub taken: i64,
+    pub previous: Option<i64>,
+    pub next: Option<i64>,
 }
 
-pub fn get_age(group: &str, year: i32) -> Result<Duration, Box<dyn Error>> {
-    let path = get_data_path(group, year);
-    if !path.exists() {
-        return Ok(Duration::MAX);
+/// Load the latest snapshot that was taken at or before `at`
+///
+/// A meta board is shown with the latest snapshot of each of its parts.
+pub fn load_snapshot(group: &str, year: i32, at: i64) -> Result<Option<Snapshot>, Box<dyn Error>> {
+    let ids = board_ids(group);
+    if ids.len() > 1 {
+        let mut snapshots = Vec::new();
+        for id in ids {
+            snapshots.extend(load_snapshot(id, year, at)?);
+        }
+        let taken = match snapshots.iter().map(|snapshot| snapshot.taken).max() {
+            Some(taken) => taken,
+            None => return Ok(None),
+        };
+        // The previous snapshot of the meta board is whenever any of the parts changed before
+        let previous = snapshots
+            .iter()
+            .filter_map(|snapshot| match snapshot.taken == taken {
+                true => snapshot.previous,
+                false => Some(snapshot.taken),
+            })
+            .max();
+        let next = snapshots.iter().filter_map(|snapshot| snapshot.next).min();
+        let leaderboards = snapshots
+            .into_iter()
+            .map(|snapshot| snapshot.leaderboard)
+            .collect();
+        return Ok(
+            Leaderboard::merge(leaderboards).map(|leaderboard| Snapshot {
+                leaderboard,
+                taken,
+                previous,
+                next,
+            }),
+        );
+    }
+
+    let store = get_store();
+    let times = store.snapshots(group, year)?;
+    let index = times.partition_point(|taken| *taken <= at);
+    if index == 0 {
+        return Ok(None);
     }
-    let time = path.metadata()?.modified()?;
-    Ok(SystemTime::now().duration_since(time)?)
+    let taken = times[index - 1];
+    Ok(store
+        .load_snapshot(group, year, taken)?
+        .map(|leaderboard| Snapshot {
+            leaderboard,
+            taken,
+            previous: index.checked_sub(2).map(|i| times[i]),
+            next: times.get(index).copied(),
+        }))
 }
 
-fn get_pub_data_path(id: &str) -> PathBuf {
-    let mut path = get_cache_path();
-    path.push("pub");
-    path.push(format!("{id}.json"));
-    path
+pub fn load_leaderboard(group: &str, year: i32) -> Result<Leaderboard, Box<dyn Error>> {
+    get_store().load_leaderboard(group, year)?.ok_or_else(|| {
+        Box::new(io::Error::new(
+            io::ErrorKind::NotFound,
+            "The leaderboard has not been saved",
+        )) as Box<dyn Error>
+    })
+}
+
+#[inline]
+pub fn get_age(group: &str, year: i32) -> Result<Duration, Box<dyn Error>> {
+    get_store().get_age(group, year)
 }
 
 pub fn load_pub_leaderboard(id: &str) -> Result<PublicLeaderboard, Box<dyn Error>> {
-    let path = get_pub_data_path(id);
+    let mut pub_board = get_store().load_pub_leaderboard(id)?.ok_or_else(|| {
+        Box::new(io::Error::new(
+            io::ErrorKind::NotFound,
+            "The public leaderboard does not exist",
+        )) as Box<dyn Error>
+    })?;
+    pub_board.session = decrypt(&load_key()?, &pub_board.session)?;
+    Ok(pub_board)
+}
 
-    let mut f = File::open(path)?;
-    let mut contents = String::new();
-    f.read_to_string(&mut contents)?;
-    Ok(serde_json::from_str(&contents)?)
+/// Lock a public leaderboard while it is loaded, changed and saved again
+///
+/// Without the lock, two changes made at the same time would lose one of them.
+pub fn lock_pub_leaderboard(token: &str) -> Result<FileLock, Box<dyn Error>> {
+    FileLock::lock(get_store().lock_dir().join(format!("pub-{token}.lock")))
 }
 
 pub fn save_pub_leaderboard(
     id: &str,
     leaderboard: &PublicLeaderboard,
 ) -> Result<(), Box<dyn Error>> {
-    let path = get_pub_data_path(id);
-    let parent = path.parent().unwrap();
-    if !parent.exists() {
-        fs::create_dir_all(parent)?;
-    }
-    let mut f = File::create(path)?;
-    let val = serde_json::to_string(&leaderboard)?;
-    f.write_all(val.as_bytes())?;
-    Ok(())
+    let stored = PublicLeaderboard {
+        session: encrypt(&load_key()?, &leaderboard.session)?,
+        ..leaderboard.clone()
+    };
+    get_store().save_pub_leaderboard(id, &stored)
 }
 
 #[inline]
 pub fn pub_leaderboard_exists(id: &str) -> bool {
-    get_pub_data_path(id).exists()
+    get_store().pub_leaderboard_exists(id).unwrap_or(false)
+}
+
+/// Generate a token that isn't used by any public leaderboard
+pub fn new_pub_token() -> String {
+    loop {
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+        if !pub_leaderboard_exists(&token) {
+            return token;
+        }
+    }
 }
 
 pub fn find_pub_leaderboard(group: &str) -> Result<Option<PublicLeaderboard>, Box<dyn Error>> {
-    let mut path = get_cache_path();
-    path.push("pub");
-    if !path.exists() {
-        return Ok(None);
+    match get_store().find_pub_leaderboard(group)? {
+        Some(mut pub_board) => {
+            pub_board.session = decrypt(&load_key()?, &pub_board.session)?;
+            Ok(Some(pub_board))
+        }
+        None => Ok(None),
     }
+}
 
-    for child in path.read_dir()? {
-        let child = child?.path();
-        if !child.is_file() {
-            continue;
+pub fn load_identity(hash: &str) -> Result<Option<Identity>, Box<dyn Error>> {
+    match get_store().load_identity(hash)? {
+        Some(mut identity) => {
+            identity.session = decrypt(&load_key()?, &identity.session)?;
+            Ok(Some(identity))
         }
-        let mut f = File::open(&child)?;
-        let mut contents = String::new();
-        f.read_to_string(&mut contents)?;
-        if let Ok(pub_board) = serde_json::from_str::<PublicLeaderboard>(&contents) {
-            if pub_board.id == group {
-                return Ok(Some(pub_board));
-            }
+        None => Ok(None),
+    }
+}
+
+pub fn save_identity(identity: &Identity) -> Result<(), Box<dyn Error>> {
+    let stored = Identity {
+        session: encrypt(&load_key()?, &identity.session)?,
+        ..identity.clone()
+    };
+    get_store().save_identity(&stored)
+}
+
+#[inline]
+pub fn delete_identity(hash: &str) -> Result<(), Box<dyn Error>> {
+    get_store().delete_identity(hash)
+}
+
+/// Read a leaderboard downloaded from adventofcode.com, along with its year
+pub fn parse_leaderboard(contents: &str) -> Result<(Leaderboard, i32), Box<dyn Error>> {
+    let leaderboard: Leaderboard = serde_json::from_str(contents)?;
+    let year = leaderboard
+        .event
+        .parse::<i32>()
+        .ok()
+        .filter(|year| *year >= 2015)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid event {}", leaderboard.event),
+            )
+        })?;
+    Ok((leaderboard, year))
+}
+
+/// Save a downloaded leaderboard as a view-only public leaderboard
+///
+/// Importing the same leaderboard again updates it and keeps its token.
+pub fn import_leaderboard(contents: &str) -> Result<(PublicLeaderboard, i32), Box<dyn Error>> {
+    let (leaderboard, year) = parse_leaderboard(contents)?;
+    let id = leaderboard.owner_id.to_string();
+
+    let pub_board = match find_pub_leaderboard(&id)? {
+        Some(pub_board) if pub_board.is_view_only() => pub_board,
+        Some(_) => {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Leaderboard {id} is already published with a session"),
+            )))
+        }
+        None => PublicLeaderboard::new(new_pub_token(), &id, ""),
+    };
+
+    save_leaderboard(&leaderboard, &id, year)?;
+    save_pub_leaderboard(&pub_board.token, &pub_board)?;
+    Ok((pub_board, year))
+}
+
+/// Encrypt any session keys that are still stored as plaintext
+///
+/// Returns the number of records that were encrypted.
+pub fn encrypt_sessions() -> Result<usize, Box<dyn Error>> {
+    let mut count = 0;
+
+    for pub_board in get_store().pub_leaderboards()? {
+        if !is_encrypted(&pub_board.session) {
+            save_pub_leaderboard(&pub_board.token, &pub_board)?;
+            count += 1;
+        }
+    }
+
+    for identity in get_store().identities()? {
+        if !is_encrypted(&identity.session) {
+            save_identity(&identity)?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Why a cached leaderboard is being shown instead of a fresh one
+#[derive(Debug)]
+pub struct Stale {
+    /// When the cached leaderboard was saved
+    pub saved: DateTime<FixedOffset>,
+    /// Why the leaderboard couldn't be refreshed
+    pub reason: String,
+}
+
+impl Stale {
+    fn new(age: Duration, err: &FetchError) -> Self {
+        let now = DateTime::<Utc>::from(SystemTime::now());
+        let saved =
+            now - chrono::Duration::from_std(age).unwrap_or_else(|_| chrono::Duration::zero());
+        Self {
+            saved: DateTime::from_utc(saved.naive_utc(), est_offset()),
+            reason: err.to_string(),
+        }
+    }
+}
+
+pub struct FetchedLeaderboard {
+    pub leaderboard: Leaderboard,
+    /// Set when the refresh failed and the cached leaderboard is used instead
+    pub stale: Option<Stale>,
+}
+
+impl From<Leaderboard> for FetchedLeaderboard {
+    fn from(leaderboard: Leaderboard) -> Self {
+        Self {
+            leaderboard,
+            stale: None,
         }
     }
+}
 
-    Ok(None)
+/// Check if a cached leaderboard can be used without fetching it again
+///
+/// A `max_age` of `None` means the cache never expires.
+fn is_fresh(age: Duration, max_age: Option<Duration>) -> bool {
+    age != Duration::MAX && max_age.is_none_or(|max_age| age <= max_age)
 }
 
-pub fn get_leaderboard(session: &str, year: i32, id: &str) -> Result<Leaderboard, Box<dyn Error>> {
+/// Load the leaderboard from the cache, fetching it if it is older than `max_age`
+///
+/// Only one process will refresh a leaderboard at a time. While it is being
+/// refreshed, everyone else gets the stale copy, or waits for the refresh to
+/// finish if there is nothing cached yet.
+///
+/// If the refresh fails, the cached leaderboard is used along with the reason
+/// it couldn't be refreshed. Leaderboards that don't exist or can't be seen
+/// are remembered for as long as a leaderboard would be cached, so they aren't
+/// asked for on every view.
+fn refresh_leaderboard(
+    session: &str,
+    year: i32,
+    id: &str,
+    max_age: Option<Duration>,
+) -> Result<FetchedLeaderboard, FetchError> {
+    let age = get_age(id, year)?;
+    if is_fresh(age, max_age) {
+        return Ok(load_leaderboard(id, year)?.into());
+    }
+    let misses = Misses::new(get_store().lock_dir());
+    if age == Duration::MAX {
+        if let Some(miss) = misses.get(session, id, year) {
+            return Err(miss.into());
+        }
+    }
+
+    let lock_path = get_store().lock_dir().join(format!("{id}-{year}.lock"));
+    let _lock = match FileLock::try_lock(&lock_path)? {
+        Some(lock) => lock,
+        None if age != Duration::MAX => return Ok(load_leaderboard(id, year)?.into()),
+        None => FileLock::lock(&lock_path)?,
+    };
+
+    // Someone else may have refreshed it before we got the lock
+    let age = get_age(id, year)?;
+    if is_fresh(age, max_age) {
+        return Ok(load_leaderboard(id, year)?.into());
+    }
+
+    match fetch_leaderboard(session, id, year) {
+        Ok(leaderboard) => {
+            save_leaderboard(&leaderboard, id, year)?;
+            Ok(leaderboard.into())
+        }
+        Err(err) if age != Duration::MAX => {
+            eprintln!("Could not refresh {id}-{year}: {err}");
+            Ok(FetchedLeaderboard {
+                leaderboard: load_leaderboard(id, year)?,
+                stale: Some(Stale::new(age, &err)),
+            })
+        }
+        Err(err) => {
+            if let Some(miss) = Miss::from_error(&err) {
+                // Finished years never expire, but someone may still join the leaderboard
+                let ttl = max_age.unwrap_or(Duration::from_secs(get_config().cache.inactive));
+                misses.insert(session, id, year, miss, ttl)?;
+            }
+            Err(err)
+        }
+    }
+}
+
+pub fn get_leaderboard(
+    session: &str,
+    year: i32,
+    id: &str,
+) -> Result<FetchedLeaderboard, FetchError> {
+    let ids = board_ids(id);
+    if ids.len() > 1 {
+        let mut leaderboards = Vec::new();
+        let mut stale = None;
+        for id in ids {
+            let fetched = get_leaderboard(session, year, id)?;
+            leaderboards.push(fetched.leaderboard);
+            stale = stale.or(fetched.stale);
+        }
+        return Ok(FetchedLeaderboard {
+            leaderboard: Leaderboard::merge(leaderboards).ok_or(FetchError::NotFound)?,
+            stale,
+        });
+    }
+
+    if session.is_empty() {
+        // Imported leaderboards can't be refreshed
+        return match get_store().load_leaderboard(id, year)? {
+            Some(leaderboard) => Ok(leaderboard.into()),
+            None => Err(FetchError::NotFound),
+        };
+    }
+
     let now = DateTime::<Utc>::from(SystemTime::now());
     let now = DateTime::<FixedOffset>::from_utc(now.naive_utc(), est_offset());
 
-    let age = get_age(id, year)?;
-    let start = NaiveDate::from_ymd_opt(year, 12, 1).unwrap();
-    let end = NaiveDate::from_ymd_opt(year, 12, 25).unwrap();
-
-    Ok(if now.date_naive() >= start && now.date_naive() <= end {
-        // The competition is active
-        let start = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
-        let end = NaiveTime::from_hms_opt(1, 0, 0).unwrap();
-        if now.time() >= start && now.time() <= end {
-            // The competition has recently started
-
-            // Keep a 60 second cache
-            if age.as_secs() > 60 {
-                let leaderboard = fetch_leaderboard(session, id, year)?;
-                save_leaderboard(&leaderboard, id, year)?;
-                leaderboard
-            } else {
-                load_leaderboard(id, year)?
+    let max_age = get_config().cache.max_age(year, now);
+    refresh_leaderboard(session, year, id, max_age)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    const JSON: &str = "Content-Type: application/json";
+
+    /// Serve a single http response, returning the request that was made
+    fn mock_server(
+        status: &str,
+        headers: &str,
+        body: &'static str,
+    ) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let status = status.to_owned();
+        let headers = headers.to_owned();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                request += &line;
             }
-        } else {
-            // Keep a 15 minute cache
-            if age.as_secs() > 900 {
-                let leaderboard = fetch_leaderboard(session, id, year)?;
-                save_leaderboard(&leaderboard, id, year)?;
-                leaderboard
-            } else {
-                load_leaderboard(id, year)?
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\n{headers}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            request
+        });
+        (format!("http://{addr}"), handle)
+    }
+
+    #[test]
+    fn test_request_leaderboard() {
+        let (base_url, handle) = mock_server(
+            "200 OK",
+            JSON,
+            r#"{"event":"2022","owner_id":1,"members":{}}"#,
+        );
+        let aoc = AocConfig {
+            base_url,
+            user_agent: "aocgem-test".into(),
+            contact: Some("me@example.com".into()),
+        };
+
+        let leaderboard = request_leaderboard(&aoc, "abc", "123", 2022).unwrap();
+        assert_eq!(leaderboard.event, "2022");
+
+        let request = handle.join().unwrap().to_lowercase();
+        assert!(request.starts_with("get /2022/leaderboard/private/view/123.json "));
+        assert!(request.contains("user-agent: aocgem-test by me@example.com"));
+        assert!(request.contains("cookie: session=abc"));
+    }
+
+    fn request_error(status: &str, headers: &str, body: &'static str) -> FetchError {
+        let (base_url, handle) = mock_server(status, headers, body);
+        let aoc = AocConfig {
+            base_url,
+            ..Default::default()
+        };
+        let err = request_leaderboard(&aoc, "abc", "123", 2022).unwrap_err();
+        handle.join().unwrap();
+        err
+    }
+
+    #[test]
+    fn test_snapshot_dedup() {
+        let store = crate::store::SqliteStore::in_memory().unwrap();
+        let (first, _) =
+            parse_leaderboard(r#"{"event":"2022","owner_id":1,"members":{}}"#).unwrap();
+        let (second, _) =
+            parse_leaderboard(r#"{"event":"2022","owner_id":2,"members":{}}"#).unwrap();
+
+        snapshot_leaderboard(&store, &first, "1", 2022, 100).unwrap();
+        snapshot_leaderboard(&store, &first, "1", 2022, 200).unwrap();
+        snapshot_leaderboard(&store, &second, "1", 2022, 300).unwrap();
+        snapshot_leaderboard(&store, &second, "1", 2022, 400).unwrap();
+
+        assert_eq!(store.snapshots("1", 2022).unwrap(), vec![100, 300]);
+    }
+
+    #[test]
+    fn test_parse_leaderboard() {
+        let (leaderboard, year) =
+            parse_leaderboard(r#"{"event":"2021","owner_id":42,"members":{}}"#).unwrap();
+        assert_eq!(leaderboard.owner_id, 42);
+        assert_eq!(year, 2021);
+
+        assert!(parse_leaderboard(r#"{"event":"latest","owner_id":42,"members":{}}"#).is_err());
+        assert!(parse_leaderboard("<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_request_missing_leaderboard() {
+        let err = request_error("404 Not Found", JSON, "");
+        assert!(matches!(err, FetchError::NotFound));
+    }
+
+    #[test]
+    fn test_request_expired_session() {
+        let err = request_error("302 Found", "Location: /auth/login", "");
+        assert!(matches!(err, FetchError::Unauthorized));
+
+        let err = request_error(
+            "200 OK",
+            "Content-Type: text/html",
+            "<a href=\"/auth/login\">[Log In]</a>",
+        );
+        assert!(matches!(err, FetchError::Unauthorized));
+    }
+
+    #[test]
+    fn test_request_rate_limited() {
+        let err = request_error("429 Too Many Requests", "Retry-After: 120", "");
+        match err {
+            FetchError::RateLimited(limited) => {
+                assert_eq!(limited.retry_after, Duration::from_secs(120))
             }
+            err => panic!("expected to be rate limited, got {err:?}"),
         }
-    } else {
-        // Keep a 1 hour cache
-        if age.as_secs() > 3600 {
-            let leaderboard = fetch_leaderboard(session, id, year)?;
-            save_leaderboard(&leaderboard, id, year)?;
-            leaderboard
-        } else {
-            load_leaderboard(id, year)?
-        }
-    })
+
+        let err = request_error("429 Too Many Requests", JSON, "");
+        assert!(matches!(err, FetchError::RateLimited(_)));
+    }
+
+    #[test]
+    fn test_request_server_error() {
+        let err = request_error("503 Service Unavailable", "Content-Type: text/html", "");
+        assert!(matches!(err, FetchError::Network(_)));
+        assert!(err.to_string().contains("503"));
+
+        let err = request_error("500 Internal Server Error", JSON, "");
+        assert!(matches!(err, FetchError::Network(_)));
+    }
+
+    #[test]
+    fn test_request_unexpected_status() {
+        let err = request_error("400 Bad Request", JSON, "");
+        assert!(matches!(err, FetchError::Upstream(StatusCode::BAD_REQUEST)));
+
+        let err = request_error("418 I'm a teapot", "Content-Type: text/html", "");
+        assert!(matches!(err, FetchError::Upstream(_)));
+    }
+
+    #[test]
+    fn test_request_member_id() {
+        let (base_url, handle) = mock_server(
+            "200 OK",
+            "Content-Type: text/html",
+            r#"<label for="display_anonymous">(anonymous user #1234567)</label>"#,
+        );
+        let aoc = AocConfig {
+            base_url,
+            ..Default::default()
+        };
+        assert_eq!(request_member_id(&aoc, "abc").unwrap(), 1234567);
+        let request = handle.join().unwrap().to_lowercase();
+        assert!(request.starts_with("get /settings "));
+
+        let (base_url, handle) = mock_server("302 Found", "Location: /auth/login", "");
+        let aoc = AocConfig {
+            base_url,
+            ..Default::default()
+        };
+        let err = request_member_id(&aoc, "abc").unwrap_err();
+        handle.join().unwrap();
+        assert!(matches!(err, FetchError::Unauthorized));
+    }
+
+    #[test]
+    fn test_request_no_access() {
+        let err = request_error("302 Found", "Location: /2022/leaderboard/private", "");
+        assert!(matches!(err, FetchError::Forbidden));
+    }
 }
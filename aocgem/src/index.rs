@@ -0,0 +1,168 @@
+use std::{collections::HashMap, error::Error, fs::File, io::Read, path::PathBuf};
+
+use crate::{leaderboard::PublicLeaderboard, state::StateFile};
+
+/// An index from AoC leaderboard ids to public leaderboard tokens
+///
+/// The index is stored next to the directory of public leaderboards, and can
+/// always be rebuilt from that directory.
+pub struct PubIndex {
+    dir: PathBuf,
+    state: StateFile,
+}
+
+impl PubIndex {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self {
+            state: StateFile::new(dir.with_extension("index.json")),
+            dir,
+        }
+    }
+
+    /// Hold an exclusive lock on the index while running `f`
+    fn locked<T>(
+        &self,
+        f: impl FnOnce() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let _lock = self.state.lock()?;
+        f()
+    }
+
+    fn read(&self) -> Option<HashMap<String, String>> {
+        self.state.read()
+    }
+
+    fn write(&self, index: &HashMap<String, String>) -> Result<(), Box<dyn Error>> {
+        self.state.write(index)
+    }
+
+    fn scan(&self) -> Result<HashMap<String, String>, Box<dyn Error>> {
+        let mut index = HashMap::new();
+        if !self.dir.exists() {
+            return Ok(index);
+        }
+
+        for child in self.dir.read_dir()? {
+            let child = child?.path();
+            if !child.is_file() || child.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let mut f = File::open(&child)?;
+            let mut contents = String::new();
+            f.read_to_string(&mut contents)?;
+            if let Ok(pub_board) = serde_json::from_str::<PublicLeaderboard>(&contents) {
+                index.insert(pub_board.id, pub_board.token);
+            }
+        }
+        Ok(index)
+    }
+
+    /// Rebuild the index by reading every public leaderboard
+    pub fn rebuild(&self) -> Result<HashMap<String, String>, Box<dyn Error>> {
+        self.locked(|| {
+            let index = self.scan()?;
+            self.write(&index)?;
+            Ok(index)
+        })
+    }
+
+    /// Find the token for a leaderboard id
+    ///
+    /// The index is rebuilt if it is missing or corrupted.
+    pub fn get(&self, id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let index = match self.read() {
+            Some(index) => index,
+            None => self.rebuild()?,
+        };
+        Ok(index.get(id).cloned())
+    }
+
+    pub fn insert(&self, id: &str, token: &str) -> Result<(), Box<dyn Error>> {
+        self.locked(|| {
+            let (mut index, missing) = match self.read() {
+                Some(index) => (index, false),
+                None => (self.scan()?, true),
+            };
+            if missing || index.get(id).map(String::as_str) != Some(token) {
+                index.insert(id.into(), token.into());
+                self.write(&index)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, sync::Arc, thread};
+
+    use super::*;
+
+    fn write_board(dir: &std::path::Path, token: &str, id: &str) {
+        fs::create_dir_all(dir).unwrap();
+        let board = PublicLeaderboard::new(token, id, "session");
+        fs::write(
+            dir.join(format!("{token}.json")),
+            serde_json::to_string(&board).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let tmp = tempfile::tempdir().unwrap();
+        let index = PubIndex::new(tmp.path().join("pub"));
+
+        assert_eq!(index.get("123").unwrap(), None);
+        index.insert("123", "abc").unwrap();
+        assert_eq!(index.get("123").unwrap().as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_rebuild_corrupted() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("pub");
+        write_board(&dir, "abc", "123");
+        write_board(&dir, "def", "456");
+
+        let index = PubIndex::new(&dir);
+        fs::write(dir.with_extension("index.json"), "{not json").unwrap();
+
+        assert_eq!(index.get("123").unwrap().as_deref(), Some("abc"));
+        assert_eq!(index.get("456").unwrap().as_deref(), Some("def"));
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = Arc::new(tmp.path().join("pub"));
+
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let dir = dir.clone();
+                thread::spawn(move || {
+                    // Every thread opens its own index, like separate cgi processes
+                    let index = PubIndex::new(dir.as_path());
+                    for i in 0..25 {
+                        index
+                            .insert(&format!("{t}-{i}"), &format!("token{t}-{i}"))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let index = PubIndex::new(dir.as_path());
+        let all = index.read().unwrap();
+        assert_eq!(all.len(), 200);
+        for t in 0..8 {
+            for i in 0..25 {
+                assert_eq!(all[&format!("{t}-{i}")], format!("token{t}-{i}"));
+            }
+        }
+    }
+}
@@ -1,12 +1,21 @@
 use std::collections::HashMap;
 
-use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+use crate::scoring::{local_scores, Scheme};
+
 pub fn est_offset() -> FixedOffset {
     FixedOffset::west_opt(5 * 3600).unwrap()
 }
 
+/// Convert a unix timestamp to EST
+pub fn est_from_timestamp(timestamp: i64) -> DateTime<FixedOffset> {
+    let dt = NaiveDateTime::from_timestamp_opt(timestamp, 0).unwrap();
+    DateTime::from_utc(dt, est_offset())
+}
+
 pub fn est_midnight(day: NaiveDate) -> DateTime<FixedOffset> {
     let midnight = NaiveDateTime::new(
         day,
@@ -15,6 +24,77 @@ pub fn est_midnight(day: NaiveDate) -> DateTime<FixedOffset> {
     DateTime::from_local(midnight, est_offset())
 }
 
+/// The year of the latest event, which is this year's once December starts
+pub fn latest_year(now: DateTime<FixedOffset>) -> i32 {
+    match now.month() {
+        12 => now.year(),
+        _ => now.year() - 1,
+    }
+}
+
+/// Parse a team entered as its name and the ids of its members, like `Backend: 123, 456`
+///
+/// A team without any ids is returned with an empty list.
+pub fn parse_team(input: &str) -> Option<(String, Vec<i32>)> {
+    let (name, ids) = input.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut ids: Vec<i32> = ids
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().ok())
+        .collect::<Option<_>>()?;
+    ids.sort_unstable();
+    ids.dedup();
+    Some((name.to_owned(), ids))
+}
+
+/// Count a duration from `start` seconds after the unlock instead of the unlock
+///
+/// Stars earned before the member usually starts are counted from the unlock.
+pub fn adjust_duration(duration: Duration, start: u32) -> Duration {
+    let start = Duration::seconds(start as i64);
+    if duration > start {
+        duration - start
+    } else {
+        duration
+    }
+}
+
+/// Parse when a member usually starts into seconds after the unlock
+///
+/// The time is `HH:MM` in EST, or followed by a UTC offset like `07:00 +01:00`
+/// or `07:00 UTC+1`.
+pub fn parse_start(input: &str) -> Option<u32> {
+    let regex =
+        Regex::new(r"^(\d{1,2}):(\d{2})(?:\s*(?:UTC|GMT)?\s*([+-])(\d{1,2})(?::?(\d{2}))?)?$")
+            .unwrap();
+    let captures = regex.captures(input.trim())?;
+    let number = |i: usize| captures.get(i).map_or(Ok(0), |m| m.as_str().parse::<i64>());
+
+    let (hours, minutes) = (number(1).ok()?, number(2).ok()?);
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    let offset = match captures.get(3).map(|m| m.as_str()) {
+        Some(sign) => {
+            let offset = number(4).ok()? * 3600 + number(5).ok()? * 60;
+            if sign == "-" {
+                -offset
+            } else {
+                offset
+            }
+        }
+        None => est_offset().local_minus_utc() as i64,
+    };
+
+    // Move the local time to EST, which is when puzzles unlock
+    let est = hours * 3600 + minutes * 60 - offset + est_offset().local_minus_utc() as i64;
+    Some(est.rem_euclid(24 * 3600) as u32)
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Completion {
     pub get_star_ts: i64,
@@ -23,8 +103,7 @@ pub struct Completion {
 
 impl Completion {
     pub fn completion_time(&self) -> DateTime<FixedOffset> {
-        let dt = NaiveDateTime::from_timestamp_opt(self.get_star_ts, 0).unwrap();
-        DateTime::from_utc(dt, est_offset())
+        est_from_timestamp(self.get_star_ts)
     }
 
     pub fn duration(&self, start_date: NaiveDate) -> Duration {
@@ -83,17 +162,35 @@ impl Member {
     ) -> HashMap<u32, (Option<Duration>, Option<Duration>)> {
         let mut times = HashMap::new();
         for (day, completion) in &self.completion_day_level {
-            if let Ok(day) = u32::from_str_radix(day, 10) {
+            if let Ok(day) = day.parse::<u32>() {
                 times.insert(day, Self::calc_completion_time(year, day, completion));
             }
         }
         times
     }
 
+    /// The time to finish a day counted from when the member started instead of the unlock
+    pub fn adjusted_completion_time(&self, day: u32, year: i32, start: u32) -> Option<Duration> {
+        match self.completion_time(day, year) {
+            (Some(a), b) => Some(adjust_duration(a, start) + b.unwrap_or_else(Duration::zero)),
+            _ => None,
+        }
+    }
+
+    /// The total time counted from when the member started each day instead of the unlock
+    pub fn total_adjusted_time(&self, year: i32, start: u32) -> Option<Duration> {
+        self.completion_times(year)
+            .into_values()
+            .filter_map(|(a, b)| {
+                Some(adjust_duration(a?, start) + b.unwrap_or_else(Duration::zero))
+            })
+            .reduce(|total, time| total + time)
+    }
+
     pub fn total_completion_time(&self, year: i32) -> Option<Duration> {
         let mut total: Option<Duration> = None;
         for (day, completion) in &self.completion_day_level {
-            if let Ok(day) = u32::from_str_radix(day, 10) {
+            if let Ok(day) = day.parse::<u32>() {
                 let (a, b) = Self::calc_completion_time(year, day, completion);
                 if let Some(a) = a {
                     total = Some(match total {
@@ -120,11 +217,85 @@ pub struct Leaderboard {
     pub members: HashMap<String, Member>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Leaderboard {
+    /// Combine several leaderboards of the same year into one
+    ///
+    /// Members of more than one leaderboard are only counted once, and the
+    /// local scores are worked out again for everyone that is left.
+    pub fn merge(leaderboards: Vec<Leaderboard>) -> Option<Leaderboard> {
+        let mut leaderboards = leaderboards.into_iter();
+        let mut merged = leaderboards.next()?;
+        for leaderboard in leaderboards {
+            for (id, member) in leaderboard.members {
+                merged.members.entry(id).or_insert(member);
+            }
+        }
+
+        let scores = local_scores(&merged, i64::MAX);
+        for member in merged.members.values_mut() {
+            member.local_score = scores[&member.id];
+        }
+        Some(merged)
+    }
+}
+
+/// The AoC leaderboard ids that make up a leaderboard id
+///
+/// A meta board combines several leaderboards, its id is their ids joined by `+`.
+pub fn board_ids(id: &str) -> Vec<&str> {
+    id.split('+').collect()
+}
+
+/// Turn one or more leaderboard ids entered by the user into a leaderboard id
+///
+/// The ids are sorted so the same leaderboards always make the same meta board.
+pub fn parse_board_ids(input: &str) -> Option<String> {
+    let mut ids: Vec<u64> = input
+        .split(|c: char| c == ',' || c == '+' || c.is_whitespace())
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().ok())
+        .collect::<Option<_>>()?;
+    ids.sort_unstable();
+    ids.dedup();
+    match ids.is_empty() {
+        true => None,
+        false => Some(
+            ids.iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join("+"),
+        ),
+    }
+}
+
+/// How a public leaderboard is shown
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Settings {
+    /// How the members are ranked
+    #[serde(default)]
+    pub scheme: Scheme,
+    /// When members usually start, in seconds after a puzzle unlocks, keyed by `Member.id`
+    #[serde(default)]
+    pub starts: HashMap<String, u32>,
+    /// The `Member.id`s in each team, keyed by the team's name
+    #[serde(default)]
+    pub teams: HashMap<String, Vec<i32>>,
+}
+
+impl Settings {
+    /// When the member usually starts, at the unlock if they haven't said
+    pub fn start(&self, member: i32) -> u32 {
+        self.starts.get(&member.to_string()).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PublicLeaderboard {
     pub token: String,
     pub id: String,
     pub session: String,
+    #[serde(flatten)]
+    pub settings: Settings,
 }
 
 impl PublicLeaderboard {
@@ -137,6 +308,153 @@ impl PublicLeaderboard {
             token: token.into(),
             id: id.into(),
             session: session.into(),
+            settings: Settings::default(),
+        }
+    }
+
+    /// Imported leaderboards have no session, so they are never refreshed
+    #[inline]
+    pub fn is_view_only(&self) -> bool {
+        self.session.is_empty()
+    }
+}
+
+/// A session key bound to a client certificate
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Identity {
+    pub hash: String,
+    pub session: String,
+    /// The `Member.id` of the session, once it has been looked up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member: Option<i32>,
+}
+
+impl Identity {
+    pub fn new(hash: impl Into<String>, session: impl Into<String>) -> Self {
+        Self {
+            hash: hash.into(),
+            session: session.into(),
+            member: None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// An empty 2022 leaderboard owned by member 1
+    pub fn sample_leaderboard() -> Leaderboard {
+        Leaderboard {
+            event: "2022".into(),
+            owner_id: 1,
+            members: HashMap::new(),
         }
     }
+
+    /// A 2022 leaderboard of `members`
+    pub fn sample_board(members: Vec<Member>) -> Leaderboard {
+        Leaderboard {
+            members: members
+                .into_iter()
+                .map(|member| (member.id.to_string(), member))
+                .collect(),
+            ..sample_leaderboard()
+        }
+    }
+
+    /// A member with a star for every `(day, part, get_star_ts, star_index)`
+    ///
+    /// The scores are 0, tests that need them set them afterwards.
+    pub fn sample_member(id: i32, name: &str, stars: &[(u32, u32, i64, i32)]) -> Member {
+        let mut completion_day_level: HashMap<String, HashMap<String, Completion>> = HashMap::new();
+        for &(day, part, get_star_ts, star_index) in stars {
+            completion_day_level
+                .entry(day.to_string())
+                .or_default()
+                .insert(
+                    part.to_string(),
+                    Completion {
+                        get_star_ts,
+                        star_index,
+                    },
+                );
+        }
+        Member {
+            id,
+            name: name.into(),
+            global_score: 0,
+            last_star_ts: stars.iter().map(|star| star.2).max().unwrap_or(0),
+            local_score: 0,
+            stars: stars.len() as i32,
+            completion_day_level,
+        }
+    }
+
+    #[test]
+    fn test_parse_start() {
+        assert_eq!(parse_start("02:30"), Some(2 * 3600 + 30 * 60));
+        // 07:00 in Central Europe is 01:00 EST
+        assert_eq!(parse_start("07:00 +01:00"), Some(3600));
+        assert_eq!(parse_start("07:00 UTC+1"), Some(3600));
+        // 21:00 in California is 00:00 EST the next day
+        assert_eq!(parse_start("21:00 -0800"), Some(0));
+        assert_eq!(parse_start("25:00"), None);
+        assert_eq!(parse_start("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_team() {
+        assert_eq!(
+            parse_team("Backend: 456, 123 456"),
+            Some(("Backend".to_owned(), vec![123, 456]))
+        );
+        assert_eq!(parse_team(" Ops :"), Some(("Ops".to_owned(), vec![])));
+        assert_eq!(parse_team("Backend 123"), None);
+        assert_eq!(parse_team(": 123"), None);
+        assert_eq!(parse_team("Backend: Alice"), None);
+    }
+
+    #[test]
+    fn test_parse_board_ids() {
+        assert_eq!(parse_board_ids("123456"), Some("123456".to_owned()));
+        assert_eq!(
+            parse_board_ids("789, 123 456+123"),
+            Some("123+456+789".to_owned())
+        );
+        assert_eq!(parse_board_ids("private/view/123"), None);
+        assert_eq!(parse_board_ids(" "), None);
+        assert_eq!(board_ids("123+456"), vec!["123", "456"]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let member =
+            |id: i32, ts: i64, index: i32| sample_member(id, &id.to_string(), &[(1, 1, ts, index)]);
+        // Member 2 is on both leaderboards
+        let a = sample_board(vec![member(1, 100, 1), member(2, 200, 2)]);
+        let b = sample_board(vec![member(2, 200, 2), member(3, 300, 3)]);
+
+        let merged = Leaderboard::merge(vec![a, b]).unwrap();
+        assert_eq!(merged.members.len(), 3);
+        assert_eq!(merged.members["1"].local_score, 3);
+        assert_eq!(merged.members["2"].local_score, 2);
+        assert_eq!(merged.members["3"].local_score, 1);
+        assert!(Leaderboard::merge(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_latest_year() {
+        assert_eq!(latest_year(est_from_timestamp(1669870800)), 2022);
+        // Nov 30 2022
+        assert_eq!(latest_year(est_from_timestamp(1669870799)), 2021);
+    }
+
+    #[test]
+    fn test_adjust_duration() {
+        let hour = Duration::hours(1);
+        assert_eq!(adjust_duration(hour * 3, 3600), hour * 2);
+        // Solved before they usually start, so they must have started at the unlock
+        assert_eq!(adjust_duration(hour, 7200), hour);
+    }
 }
@@ -0,0 +1,64 @@
+use std::{
+    error::Error,
+    fs::{self, File, OpenOptions, TryLockError},
+    path::Path,
+};
+
+/// An advisory lock on a file, released when dropped
+pub struct FileLock {
+    file: File,
+}
+
+fn open_lock_file(path: &Path) -> Result<File, Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?)
+}
+
+impl FileLock {
+    /// Wait until the lock is available
+    pub fn lock(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let file = open_lock_file(path.as_ref())?;
+        file.lock()?;
+        Ok(Self { file })
+    }
+
+    /// Take the lock if nobody else is holding it
+    pub fn try_lock(path: impl AsRef<Path>) -> Result<Option<Self>, Box<dyn Error>> {
+        let file = open_lock_file(path.as_ref())?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(err)) => Err(Box::new(err)),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_try_lock() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("test.lock");
+
+        let lock = FileLock::try_lock(&path).unwrap();
+        assert!(lock.is_some());
+        assert!(FileLock::try_lock(&path).unwrap().is_none());
+
+        drop(lock);
+        assert!(FileLock::try_lock(&path).unwrap().is_some());
+    }
+}
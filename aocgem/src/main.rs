@@ -1,24 +1,35 @@
-use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
 
-use cgi::{get_path, Response, Result};
+use cgi::{Request, Response, Result};
 
 use route_recognizer::Router;
 use routes::{add_routes, FnRoute};
+use server::ServerOptions;
 
 pub mod cgi;
+pub mod config;
 pub mod fetch;
+pub mod index;
 pub mod leaderboard;
+pub mod lock;
+pub mod misses;
 pub mod query;
+pub mod ratelimit;
 pub mod render;
 pub mod routes;
+pub mod scoring;
+pub mod secret;
+pub mod server;
+pub mod state;
+pub mod store;
 
-fn handle() -> Result<Response> {
+fn route(request: &Request) -> Result<Response> {
     let mut router = Router::<&FnRoute>::new();
     add_routes(&mut router);
 
-    let path = get_path();
+    let path = request.path();
 
-    let m = match router.recognize(&path) {
+    let m = match router.recognize(path) {
         Ok(val) => val,
         Err(_) => {
             return Ok(Response::perm_error(format!(
@@ -28,19 +39,77 @@ fn handle() -> Result<Response> {
     };
     let params = m.params();
 
-    m.handler()(params)
+    m.handler()(request, params)
 }
 
-fn main() {
-    let response = match std::panic::catch_unwind(|| match handle() {
+/// Route the request, turning any errors or panics into a response
+pub fn handle(request: &Request) -> Response {
+    match panic::catch_unwind(AssertUnwindSafe(|| match route(request) {
         Ok(response) => response,
         Err(err) => {
-            io::stderr().write_fmt(format_args!("{err}\n")).unwrap();
+            eprintln!("{err}");
             err.into()
         }
-    }) {
+    })) {
         Ok(response) => response,
         Err(_) => Response::cgi_error("Internal Error"),
+    }
+}
+
+fn cgi_main() {
+    let response = match Request::from_env() {
+        Ok(request) => handle(&request),
+        Err(err) => {
+            eprintln!("{err}");
+            Response::cgi_error("Internal Error")
+        }
     };
     print!("{response}")
 }
+
+fn main() {
+    if let Err(err) = config::load_config()
+        .and_then(config::set_config)
+        .and_then(|_| store::open_store())
+        .and_then(store::set_store)
+    {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("serve") => {
+            if let Err(err) = ServerOptions::from_args(args).and_then(server::serve) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        Some("encrypt-sessions") => match fetch::encrypt_sessions() {
+            Ok(count) => println!("Encrypted {count} session keys"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        },
+        Some("import") => {
+            let result = match args.next() {
+                Some(path) => std::fs::read_to_string(path)
+                    .map_err(|err| err.into())
+                    .and_then(|contents| fetch::import_leaderboard(&contents)),
+                None => Err("Usage: aocgem import <leaderboard.json>".into()),
+            };
+            match result {
+                Ok((pub_board, year)) => println!(
+                    "Imported leaderboard {}, view it at /leaderboard/{}/{year}/",
+                    pub_board.id, pub_board.token
+                ),
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => cgi_main(),
+    }
+}
@@ -0,0 +1,144 @@
+use std::{collections::HashMap, error::Error, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fetch::FetchError,
+    ratelimit::session_key,
+    state::{unix_now, StateFile},
+};
+
+/// Why adventofcode.com wouldn't send a leaderboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Miss {
+    NotFound,
+    Forbidden,
+}
+
+impl Miss {
+    /// The misses worth remembering, other errors are worth trying again
+    pub fn from_error(err: &FetchError) -> Option<Self> {
+        match err {
+            FetchError::NotFound => Some(Miss::NotFound),
+            FetchError::Forbidden => Some(Miss::Forbidden),
+            _ => None,
+        }
+    }
+}
+
+impl From<Miss> for FetchError {
+    fn from(miss: Miss) -> Self {
+        match miss {
+            Miss::NotFound => FetchError::NotFound,
+            Miss::Forbidden => FetchError::Forbidden,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Entry {
+    miss: Miss,
+    expires: f64,
+}
+
+/// Leaderboards that couldn't be fetched, so they aren't asked for again until they expire
+///
+/// Whether a leaderboard can be seen depends on the session, so misses are
+/// kept for each session. Like the rate limiter, the misses are shared by
+/// every process using the same state file.
+pub struct Misses {
+    state: StateFile,
+}
+
+fn key(session: &str, group: &str, year: i32) -> String {
+    format!("{group}-{year}-{}", session_key(session))
+}
+
+impl Misses {
+    /// Keep the misses in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            state: StateFile::new(dir.into().join("misses.json")),
+        }
+    }
+
+    fn read(&self) -> HashMap<String, Entry> {
+        self.state.read().unwrap_or_default()
+    }
+
+    /// The miss for the leaderboard, if it hasn't expired yet
+    pub fn get(&self, session: &str, group: &str, year: i32) -> Option<Miss> {
+        self.get_at(session, group, year, unix_now())
+    }
+
+    fn get_at(&self, session: &str, group: &str, year: i32, now: f64) -> Option<Miss> {
+        self.read()
+            .remove(&key(session, group, year))
+            .filter(|entry| entry.expires > now)
+            .map(|entry| entry.miss)
+    }
+
+    /// Remember a miss for `ttl`
+    pub fn insert(
+        &self,
+        session: &str,
+        group: &str,
+        year: i32,
+        miss: Miss,
+        ttl: Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        self.insert_at(session, group, year, miss, ttl, unix_now())
+    }
+
+    fn insert_at(
+        &self,
+        session: &str,
+        group: &str,
+        year: i32,
+        miss: Miss,
+        ttl: Duration,
+        now: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let _lock = self.state.lock()?;
+        let mut entries = self.read();
+        // Forget about the misses that have expired
+        entries.retain(|_, entry| entry.expires > now);
+        entries.insert(
+            key(session, group, year),
+            Entry {
+                miss,
+                expires: now + ttl.as_secs_f64(),
+            },
+        );
+        self.state.write(&entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_misses() {
+        let tmp = tempfile::tempdir().unwrap();
+        let misses = Misses::new(tmp.path());
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(misses.get_at("a", "123", 2016, 100.0), None);
+        misses
+            .insert_at("a", "123", 2016, Miss::NotFound, ttl, 100.0)
+            .unwrap();
+        misses
+            .insert_at("a", "123", 2017, Miss::Forbidden, ttl, 100.0)
+            .unwrap();
+
+        assert_eq!(misses.get_at("a", "123", 2016, 150.0), Some(Miss::NotFound));
+        assert_eq!(
+            misses.get_at("a", "123", 2017, 150.0),
+            Some(Miss::Forbidden)
+        );
+        // Other sessions might be able to see the leaderboard
+        assert_eq!(misses.get_at("b", "123", 2016, 150.0), None);
+        assert_eq!(misses.get_at("a", "123", 2016, 160.0), None);
+    }
+}
@@ -165,6 +165,13 @@ impl Query {
     }
 }
 
+impl Default for Query {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Display for Query {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if !self.unnamed.is_empty() {
@@ -0,0 +1,241 @@
+use std::{collections::HashMap, error::Error, fmt::Display, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::RateLimit,
+    state::{unix_now, StateFile},
+};
+
+/// Returned when a request to adventofcode.com would go over the rate limit
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = self.retry_after.as_secs().max(1);
+        f.write_fmt(format_args!(
+            "Too many requests to adventofcode.com, try again in {seconds} seconds"
+        ))
+    }
+}
+
+impl Error for RateLimited {}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Bucket {
+    tokens: f64,
+    updated: f64,
+}
+
+impl Bucket {
+    fn full(capacity: f64, now: f64) -> Self {
+        Self {
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    /// Add the tokens that have refilled since the last update
+    fn refill(&mut self, capacity: f64, per_second: f64, now: f64) {
+        let elapsed = (now - self.updated).max(0.0);
+        self.tokens = (self.tokens + elapsed * per_second).min(capacity);
+        self.updated = now;
+    }
+
+    /// How long until there is a token available
+    fn wait(&self, per_second: f64) -> Option<Duration> {
+        if self.tokens >= 1.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Buckets {
+    global: Option<Bucket>,
+    /// Keyed by a hash of the session so that the sessions are never stored
+    sessions: HashMap<String, Bucket>,
+}
+
+/// A token bucket shared by every process using the same state file
+pub struct RateLimiter {
+    state: StateFile,
+    limits: RateLimit,
+}
+
+pub fn session_key(session: &str) -> String {
+    Sha256::digest(session.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+impl RateLimiter {
+    /// Keep the state of the limiter in `dir`
+    pub fn new(dir: impl Into<PathBuf>, limits: RateLimit) -> Self {
+        Self {
+            state: StateFile::new(dir.into().join("ratelimit.json")),
+            limits,
+        }
+    }
+
+    fn read(&self) -> Buckets {
+        self.state.read().unwrap_or_default()
+    }
+
+    /// Take a token for a request with the session
+    pub fn acquire(&self, session: &str) -> Result<(), Box<dyn Error>> {
+        self.acquire_at(session, unix_now())
+    }
+
+    fn acquire_at(&self, session: &str, now: f64) -> Result<(), Box<dyn Error>> {
+        let limits = &self.limits;
+        let global_rate = limits.global_rate / 60.0;
+        let session_rate = limits.session_rate / 60.0;
+
+        let _lock = self.state.lock()?;
+        let mut buckets = self.read();
+
+        let mut global = buckets
+            .global
+            .take()
+            .unwrap_or_else(|| Bucket::full(limits.global_burst, now));
+        global.refill(limits.global_burst, global_rate, now);
+
+        let key = session_key(session);
+        let mut bucket = buckets
+            .sessions
+            .remove(&key)
+            .unwrap_or_else(|| Bucket::full(limits.session_burst, now));
+        bucket.refill(limits.session_burst, session_rate, now);
+
+        // Forget about sessions that have refilled completely
+        buckets.sessions.retain(|_, bucket| {
+            bucket.tokens + (now - bucket.updated) * session_rate < limits.session_burst
+        });
+
+        let wait = match (global.wait(global_rate), bucket.wait(session_rate)) {
+            (None, None) => {
+                global.tokens -= 1.0;
+                bucket.tokens -= 1.0;
+                None
+            }
+            (a, b) => a.max(b),
+        };
+
+        buckets.global = Some(global);
+        buckets.sessions.insert(key, bucket);
+        self.state.write(&buckets)?;
+
+        match wait {
+            Some(retry_after) => Err(Box::new(RateLimited { retry_after })),
+            None => Ok(()),
+        }
+    }
+
+    /// Stop every request for `retry_after`, when adventofcode.com asks us to slow down
+    pub fn pause(&self, retry_after: Duration) -> Result<(), Box<dyn Error>> {
+        self.pause_at(retry_after, unix_now())
+    }
+
+    fn pause_at(&self, retry_after: Duration, now: f64) -> Result<(), Box<dyn Error>> {
+        let limits = &self.limits;
+        let global_rate = limits.global_rate / 60.0;
+
+        let _lock = self.state.lock()?;
+        let mut buckets = self.read();
+
+        let mut global = buckets
+            .global
+            .take()
+            .unwrap_or_else(|| Bucket::full(limits.global_burst, now));
+        global.refill(limits.global_burst, global_rate, now);
+        // Take enough tokens that the next one refills after `retry_after`
+        global.tokens = global
+            .tokens
+            .min(1.0 - retry_after.as_secs_f64() * global_rate);
+
+        buckets.global = Some(global);
+        self.state.write(&buckets)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn limits() -> RateLimit {
+        RateLimit {
+            global_rate: 60.0,
+            global_burst: 3.0,
+            session_rate: 6.0,
+            session_burst: 2.0,
+        }
+    }
+
+    fn is_limited(result: Result<(), Box<dyn Error>>) -> Option<Duration> {
+        match result {
+            Ok(()) => None,
+            Err(err) => Some(err.downcast::<RateLimited>().unwrap().retry_after),
+        }
+    }
+
+    #[test]
+    fn test_session_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let limiter = RateLimiter::new(tmp.path(), limits());
+
+        assert!(is_limited(limiter.acquire_at("a", 100.0)).is_none());
+        assert!(is_limited(limiter.acquire_at("a", 100.0)).is_none());
+        let wait = is_limited(limiter.acquire_at("a", 100.0)).unwrap();
+        assert_eq!(wait.as_secs(), 10);
+
+        // Another session still has tokens
+        assert!(is_limited(limiter.acquire_at("b", 100.0)).is_none());
+
+        // The session refills at 6 per minute
+        assert!(is_limited(limiter.acquire_at("a", 110.0)).is_none());
+    }
+
+    #[test]
+    fn test_global_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let limiter = RateLimiter::new(tmp.path(), limits());
+
+        assert!(is_limited(limiter.acquire_at("a", 100.0)).is_none());
+        assert!(is_limited(limiter.acquire_at("b", 100.0)).is_none());
+        assert!(is_limited(limiter.acquire_at("c", 100.0)).is_none());
+        let wait = is_limited(limiter.acquire_at("d", 100.0)).unwrap();
+        assert_eq!(wait.as_secs(), 1);
+        assert!(is_limited(limiter.acquire_at("d", 101.0)).is_none());
+    }
+
+    #[test]
+    fn test_shared_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let first = RateLimiter::new(tmp.path(), limits());
+        let second = RateLimiter::new(tmp.path(), limits());
+
+        assert!(is_limited(first.acquire_at("a", 100.0)).is_none());
+        assert!(is_limited(second.acquire_at("a", 100.0)).is_none());
+        assert!(is_limited(first.acquire_at("a", 100.0)).is_some());
+    }
+
+    #[test]
+    fn test_pause() {
+        let tmp = tempfile::tempdir().unwrap();
+        let limiter = RateLimiter::new(tmp.path(), limits());
+
+        limiter.pause_at(Duration::from_secs(30), 100.0).unwrap();
+        let wait = is_limited(limiter.acquire_at("a", 100.0)).unwrap();
+        assert_eq!(wait.as_secs(), 30);
+        assert!(is_limited(limiter.acquire_at("a", 129.0)).is_some());
+        assert!(is_limited(limiter.acquire_at("a", 130.0)).is_none());
+    }
+}
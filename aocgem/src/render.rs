@@ -1,7 +1,17 @@
 use chrono::Duration;
 
+use crate::{
+    fetch::Stale,
+    leaderboard::{board_ids, est_from_timestamp},
+};
+
 pub mod day;
+pub mod export;
 pub mod main;
+pub mod member;
+pub mod ranks;
+pub mod teams;
+pub mod years;
 
 fn render_duration(duration: &Duration) -> String {
     let seconds = duration.num_seconds() % 60;
@@ -10,3 +20,42 @@ fn render_duration(duration: &Duration) -> String {
 
     format!("{hours:02}:{minutes:02}:{seconds:02}")
 }
+
+/// Link to the leaderboard on adventofcode.com, or each part of a meta board
+fn render_aoc_link(year: i32, id: &str) -> String {
+    let ids = board_ids(id);
+    if ids.len() == 1 {
+        return format!("=> https://adventofcode.com/{year}/leaderboard/private/view/{id} View the leaderboard on adventofcode.com");
+    }
+    ids.into_iter()
+        .map(|id| format!("=> https://adventofcode.com/{year}/leaderboard/private/view/{id} View leaderboard {id} on adventofcode.com"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Show a change in time with a sign, faster times are negative
+fn render_difference(difference: &Duration) -> String {
+    if *difference < Duration::zero() {
+        format!("-{}", render_duration(&-*difference))
+    } else {
+        format!("+{}", render_duration(difference))
+    }
+}
+
+/// Warn that the leaderboard is out of date
+fn render_stale(stale: &Option<Stale>) -> String {
+    match stale {
+        Some(stale) => {
+            let saved = stale.saved.format("%b %-d %H:%M EST");
+            let reason = &stale.reason;
+            format!("\n> Showing data as of {saved}, the refresh failed: {reason}\n")
+        }
+        None => String::new(),
+    }
+}
+
+/// Note which point in time a snapshot shows
+pub fn render_taken(taken: i64) -> String {
+    let taken = est_from_timestamp(taken).format("%b %-d %Y %H:%M EST");
+    format!("\n> Showing the leaderboard as of {taken}\n")
+}
@@ -1,94 +1,184 @@
 use std::cmp::Ordering;
 
 use chrono::Duration;
+use serde_json::json;
 
-use crate::cgi::{Error, Response, Result};
+use crate::cgi::{Error, Request, Response, Result};
 
-use crate::leaderboard::Leaderboard;
-use crate::{cgi::parse_query, fetch::get_leaderboard};
+use crate::fetch::{get_leaderboard, Snapshot};
+use crate::leaderboard::{Leaderboard, Member, Settings};
+use crate::scoring::{median, points};
 
-use super::render_duration;
+use super::{
+    export::{export_time, render_downloads, Format, Table},
+    render_aoc_link, render_duration, render_stale, render_taken,
+};
 
-pub fn render_table(
-    leaderboard: &Leaderboard,
+/// The local score points earned for a part of the day
+fn render_points(points: Option<i32>) -> String {
+    match points {
+        Some(points) => points.to_string(),
+        None => "-".to_owned(),
+    }
+}
+
+fn render_time(time: &Option<Duration>) -> String {
+    match time {
+        Some(d) => render_duration(d),
+        None => "--:--:--".to_owned(),
+    }
+}
+
+/// The median of the times that are known, for the last row of the table
+fn render_median(times: impl Iterator<Item = Option<Duration>>) -> String {
+    let times: Vec<_> = times.flatten().collect();
+    if times.is_empty() {
+        "--:--:--".to_owned()
+    } else {
+        render_duration(&median(times))
+    }
+}
+
+/// A member's columns in the day table
+struct DayRow<'a> {
+    member: &'a Member,
+    /// The fastest to finish both parts
+    fastest: bool,
+    total: Option<Duration>,
+    part1: Option<Duration>,
+    points1: Option<i32>,
+    part2: Option<Duration>,
+    delta: Option<Duration>,
+    points2: Option<i32>,
+    adjusted: Option<Duration>,
+}
+
+/// The members in the order of `sort_method`, and the median of the total, part 1, part 2 and delta
+fn day_rows<'a>(
+    leaderboard: &'a Leaderboard,
     sort_method: &str,
     year: i32,
     day: u32,
-) -> Result<String> {
+    settings: &Settings,
+) -> (Vec<DayRow<'a>>, [String; 4]) {
     let mut members = Vec::new();
+    let points = points(leaderboard, i64::MAX);
 
-    for (_, member) in &leaderboard.members {
-        let (part1, part2) = member.completion_time(day, year);
-        let total = match part1 {
-            Some(t) => Some(
-                t + match part2 {
-                    Some(t) => t,
-                    None => Duration::zero(),
-                },
-            ),
-            None => None,
+    for member in leaderboard.members.values() {
+        let (part1, delta) = member.completion_time(day, year);
+        let total = part1.map(|t| {
+            t + match delta {
+                Some(t) => t,
+                None => Duration::zero(),
+            }
+        });
+        // The time since the unlock, only once both stars are done
+        let part2 = delta.and(total);
+        // Stars earned at the same second are ranked like adventofcode.com does
+        let star_index = |part: &str| {
+            member
+                .completion_day_level
+                .get(&day.to_string())
+                .and_then(|parts| parts.get(part))
+                .map_or(i32::MAX, |completion| completion.star_index)
+        };
+        let (index1, index2) = (star_index("1"), star_index("2"));
+        // The total ends with the last star of the day
+        let total_index = match delta {
+            Some(_) => index2,
+            None => index1,
         };
         members.push((
             // Sorting elements
-            total,
-            part1,
-            part2,
+            (total, total_index),
+            (part1, index1),
+            (part2, index2),
+            (delta, index2),
             // Data
-            &member.name,
-            match &part1 {
-                Some(d) => render_duration(d),
-                None => "--:--:--".to_owned(),
-            },
-            match &part2 {
-                Some(d) => render_duration(d),
-                None => "--:--:--".to_owned(),
-            },
-            match &total {
-                Some(d) => render_duration(d),
-                None => "--:--:--".to_owned(),
+            DayRow {
+                member,
+                fastest: false,
+                total,
+                part1,
+                points1: points[&member.id].get(&(day, 1)).copied(),
+                part2,
+                delta,
+                points2: points[&member.id].get(&(day, 2)).copied(),
+                adjusted: member.adjusted_completion_time(day, year, settings.start(member.id)),
             },
         ));
     }
 
-    fn compare_time(a: Option<Duration>, b: Option<Duration>) -> Ordering {
-        if let Some(a) = a {
-            if let Some(b) = b {
+    fn compare_time(a: (Option<Duration>, i32), b: (Option<Duration>, i32)) -> Ordering {
+        let ordering = if let Some(a) = a.0 {
+            if let Some(b) = b.0 {
                 a.cmp(&b)
             } else {
                 Ordering::Less
             }
         } else {
-            if let Some(_) = b {
+            if b.0.is_some() {
                 Ordering::Greater
             } else {
                 Ordering::Equal
             }
-        }
+        };
+        ordering.then(a.1.cmp(&b.1))
+    }
+
+    if let Some(fastest) = members
+        .iter_mut()
+        .filter(|member| member.2 .0.is_some())
+        .min_by(|a, b| compare_time(a.2, b.2))
+    {
+        fastest.4.fastest = true;
     }
 
     match sort_method {
-        "part1" => members.sort_unstable_by(|a, b| match compare_time(a.1, b.1) {
-            Ordering::Less => Ordering::Less,
-            Ordering::Greater => Ordering::Greater,
-            Ordering::Equal => compare_time(a.2, b.2),
-        }),
-        "part2" => members.sort_unstable_by(|a, b| match compare_time(a.2, b.2) {
-            Ordering::Less => Ordering::Less,
-            Ordering::Greater => Ordering::Greater,
-            Ordering::Equal => compare_time(a.1, b.1),
-        }),
+        "part1" => members
+            .sort_unstable_by(|a, b| compare_time(a.1, b.1).then_with(|| compare_time(a.2, b.2))),
+        "part2" => members
+            .sort_unstable_by(|a, b| compare_time(a.2, b.2).then_with(|| compare_time(a.1, b.1))),
+        "delta" => members
+            .sort_unstable_by(|a, b| compare_time(a.3, b.3).then_with(|| compare_time(a.1, b.1))),
         _ => members.sort_unstable_by(|a, b| compare_time(a.0, b.0)),
     }
 
+    let medians = [
+        render_median(members.iter().map(|member| member.0 .0)),
+        render_median(members.iter().map(|member| member.1 .0)),
+        render_median(members.iter().map(|member| member.2 .0)),
+        render_median(members.iter().map(|member| member.3 .0)),
+    ];
+
+    let rows = members.into_iter().map(|(_, _, _, _, row)| row).collect();
+    (rows, medians)
+}
+
+pub fn render_table(
+    leaderboard: &Leaderboard,
+    sort_method: &str,
+    year: i32,
+    day: u32,
+    settings: &Settings,
+) -> Result<String> {
+    let (rows, medians) = day_rows(leaderboard, sort_method, year, day, settings);
+
     let mut part1_w = 0;
     let mut part2_w = 0;
+    let mut delta_w = 0;
     let mut total_w = 0;
     let mut name_w = 0;
-    for (_, _, _, name, part1, part2, total) in &members {
-        part1_w = part1.len().max(part1_w);
-        part2_w = part2.len().max(part2_w);
-        total_w = total.len().max(total_w);
-        name_w = name.len().max(name_w);
+    let mut points1_w = 0;
+    let mut points2_w = 0;
+    for row in &rows {
+        points1_w = render_points(row.points1).len().max(points1_w);
+        points2_w = render_points(row.points2).len().max(points2_w);
+        part1_w = render_time(&row.part1).len().max(part1_w);
+        part2_w = render_time(&row.part2).len().max(part2_w);
+        delta_w = render_time(&row.delta).len().max(delta_w);
+        total_w = render_time(&row.total).len().max(total_w);
+        name_w = row.member.name.len().max(name_w);
     }
 
     let mut buffer = String::new();
@@ -97,75 +187,305 @@ pub fn render_table(
     let name_t = "Name";
     let part1_t = "Part 1";
     let part2_t = "Part 2";
+    let delta_t = "Delta";
     let total_t = "Total";
+    let points_t = "Pts";
 
-    let n_w = members.len().to_string().len() + 1;
+    let n_w = rows.len().to_string().len() + 2;
+    points1_w = points1_w.max(points_t.len());
+    points2_w = points2_w.max(points_t.len());
 
     buffer += &format!(
-        "{n_t:<n_w$} {total_t:<total_w$} {part1_t:<part1_w$} {part2_t:<part2_w$} {name_t:<name_w$}"
+        "{n_t:<n_w$} {total_t:<total_w$} {part1_t:<part1_w$} {points_t:<points1_w$} {part2_t:<part2_w$} {delta_t:<delta_w$} {points_t:<points2_w$} {name_t:<name_w$}"
     );
 
-    for (i, (_, _, _, name, part1, part2, total)) in members.into_iter().enumerate() {
+    // Adjusted times are only shown once someone has set when they start
+    let adjusted = !settings.starts.is_empty();
+    if adjusted {
+        buffer += " Adjusted";
+    }
+
+    for (i, row) in rows.into_iter().enumerate() {
         let i = i + 1;
-        let i = format!("{i}.");
+        let i = if row.fastest {
+            format!("*{i}.")
+        } else {
+            format!("{i}.")
+        };
+        let DayRow {
+            member,
+            total,
+            part1,
+            points1,
+            part2,
+            delta,
+            points2,
+            adjusted: adj,
+            ..
+        } = row;
+        let name = &member.name;
+        let (total, part1, part2, delta) = (
+            render_time(&total),
+            render_time(&part1),
+            render_time(&part2),
+            render_time(&delta),
+        );
+        let (points1, points2) = (render_points(points1), render_points(points2));
         buffer += &format!(
-            "\n{i:>n_w$} {total:>total_w$} {part1:>part1_w$} {part2:>part2_w$} {name:<name_w$}"
+            "\n{i:>n_w$} {total:>total_w$} {part1:>part1_w$} {points1:>points1_w$} {part2:>part2_w$} {delta:>delta_w$} {points2:>points2_w$} {name:<name_w$}"
         );
+        if adjusted {
+            let adj = render_time(&adj);
+            buffer += &format!(" {adj:>8}");
+        }
     }
 
+    let [total, part1, part2, delta] = medians;
+    let median_t = "Median";
+    let empty = "";
+    buffer += &format!(
+        "\n\n{empty:>n_w$} {total:>total_w$} {part1:>part1_w$} {empty:>points1_w$} {part2:>part2_w$} {delta:>delta_w$} {empty:>points2_w$} {median_t}"
+    );
+
     Ok(buffer)
 }
 
-pub fn render_day(session: &str, year: i32, day: u32, id: &str) -> Result<String> {
-    if day < 1 || day > 25 {
+/// Export the day table in the same order as it is shown
+pub fn export_day(
+    request: &Request,
+    session: &str,
+    year: i32,
+    day: u32,
+    id: &str,
+    settings: &Settings,
+    format: Format,
+) -> Result<Response> {
+    if !(1..=25).contains(&day) {
+        return Err(Error::response(Response::not_found()));
+    }
+
+    let leaderboard = get_leaderboard(session, year, id)?.leaderboard;
+    export_board(request, &leaderboard, year, day, settings, format)
+}
+
+/// Export the day table as it was when the snapshot was taken
+pub fn export_day_snapshot(
+    request: &Request,
+    snapshot: &Snapshot,
+    year: i32,
+    day: u32,
+    settings: &Settings,
+    format: Format,
+) -> Result<Response> {
+    if !(1..=25).contains(&day) {
         return Err(Error::response(Response::not_found()));
     }
 
-    let leaderboard = get_leaderboard(session, year, id)?;
+    export_board(request, &snapshot.leaderboard, year, day, settings, format)
+}
 
-    let query = parse_query()?;
+/// The day table as a csv or json download
+fn export_board(
+    request: &Request,
+    leaderboard: &Leaderboard,
+    year: i32,
+    day: u32,
+    settings: &Settings,
+    format: Format,
+) -> Result<Response> {
+    let query = request.parse_query()?;
     let sort_method = query.get_value("s").unwrap_or("total");
+    let (rows, _) = day_rows(leaderboard, sort_method, year, day, settings);
 
-    let table = render_table(&leaderboard, sort_method, year, day)?;
-
-    let mut total_link = query.clone();
-    let mut part1_link = query.clone();
-    let mut part2_link = query.clone();
-
-    total_link.replace("s", "total".into());
-    part1_link.replace("s", "part1".into());
-    part2_link.replace("s", "part2".into());
-
-    let sort_options = match sort_method.as_ref() {
-        "part1" => format!(
-            "=> ?{total_link} Sort by total total
-=> ?{part2_link} Sort by part 2"
-        ),
-        "part2" => format!(
-            "=> ?{total_link} Sort by total total
-=> ?{part1_link} Sort by part 1"
-        ),
-        _ => format!(
-            "=> ?{part1_link} Sort by part 1
-=> ?{part2_link} Sort by part 2"
-        ),
-    };
-
-    let sort_name = match sort_method {
-        "part1" => "part 1",
-        "part2" => "part 2",
-        _ => "Total time",
-    };
+    let mut table = Table::new(vec![
+        "rank",
+        "id",
+        "name",
+        "total",
+        "part1",
+        "part1_points",
+        "part2",
+        "delta",
+        "part2_points",
+        "adjusted",
+        "fastest",
+    ]);
+    for (i, row) in rows.into_iter().enumerate() {
+        table.push(vec![
+            json!(i + 1),
+            json!(row.member.id),
+            json!(row.member.name),
+            export_time(row.total),
+            export_time(row.part1),
+            json!(row.points1),
+            export_time(row.part2),
+            export_time(row.delta),
+            json!(row.points2),
+            export_time(row.adjusted),
+            json!(row.fastest),
+        ]);
+    }
+    Ok(table.into_response(format)?)
+}
 
-    Ok(format!("
-=> https://adventofcode.com/{year}/leaderboard/private/view/{id} View the leaderboard on adventofcode.com
+pub fn render_day(
+    request: &Request,
+    session: &str,
+    year: i32,
+    day: u32,
+    id: &str,
+    settings: &Settings,
+) -> Result<String> {
+    if !(1..=25).contains(&day) {
+        return Err(Error::response(Response::not_found()));
+    }
+
+    let fetched = get_leaderboard(session, year, id)?;
+    let stale = render_stale(&fetched.stale);
+    render_board(
+        request,
+        &fetched.leaderboard,
+        year,
+        day,
+        id,
+        &stale,
+        settings,
+    )
+}
+
+/// Render the day as it was when the snapshot was taken
+pub fn render_day_snapshot(
+    request: &Request,
+    snapshot: &Snapshot,
+    year: i32,
+    day: u32,
+    id: &str,
+    settings: &Settings,
+) -> Result<String> {
+    if !(1..=25).contains(&day) {
+        return Err(Error::response(Response::not_found()));
+    }
+
+    let taken = render_taken(snapshot.taken);
+    render_board(
+        request,
+        &snapshot.leaderboard,
+        year,
+        day,
+        id,
+        &taken,
+        settings,
+    )
+}
 
+/// Render the day with a notice below the adventofcode.com link
+fn render_board(
+    request: &Request,
+    leaderboard: &Leaderboard,
+    year: i32,
+    day: u32,
+    id: &str,
+    notice: &str,
+    settings: &Settings,
+) -> Result<String> {
+    let query = request.parse_query()?;
+    let sort_method = query.get_value("s").unwrap_or("total");
+
+    let table = render_table(leaderboard, sort_method, year, day, settings)?;
+
+    let sorts = [
+        ("total", "total time"),
+        ("part1", "part 1"),
+        ("part2", "part 2"),
+        ("delta", "part 2 delta"),
+    ];
+    let sort_name = sorts
+        .iter()
+        .find(|(key, _)| *key == sort_method)
+        .map_or("total time", |(_, name)| name);
+    let sort_options = sorts
+        .iter()
+        .filter(|(_, name)| *name != sort_name)
+        .map(|(key, name)| {
+            let mut link = query.clone();
+            link.replace("s", (*key).into());
+            format!("=> ?{link} Sort by {name}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let downloads = render_downloads(&query);
+
+    let aoc_link = render_aoc_link(year, id);
+    Ok(format!("
+{aoc_link}
+{notice}
 > Sorting by {sort_name}
 
 ```leaderboard table
 {table}
 ```
 
+The fastest to finish both parts is marked with a `*`. Part 2 is the time since the puzzle unlocked, and the delta is the time between both stars.
+
 {sort_options}
+
+{downloads}
 "))
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::leaderboard::test::{sample_board, sample_member};
+
+    #[test]
+    fn test_ties_use_star_index() {
+        // Both finish day 1 at the same seconds, Bob's stars were recorded first
+        let leaderboard = sample_board(vec![
+            sample_member(1, "Alice", &[(1, 1, 1669870900, 3), (1, 2, 1669871000, 4)]),
+            sample_member(2, "Bob", &[(1, 1, 1669870900, 1), (1, 2, 1669871000, 2)]),
+        ]);
+
+        for sort in ["total", "part1", "part2", "delta"] {
+            let table = render_table(&leaderboard, sort, 2022, 1, &Settings::default()).unwrap();
+            let rows: Vec<_> = table.lines().collect();
+            assert!(
+                rows[1].starts_with("*1.") && rows[1].contains("Bob"),
+                "{sort}"
+            );
+            assert!(rows[2].contains("Alice"), "{sort}");
+        }
+    }
+
+    #[test]
+    fn test_total_ties_use_last_star() {
+        // Both finish day 1 at the same seconds, Bob got part 1 first and Alice part 2
+        let leaderboard = sample_board(vec![
+            sample_member(1, "Alice", &[(1, 1, 1669870900, 2), (1, 2, 1669871000, 3)]),
+            sample_member(2, "Bob", &[(1, 1, 1669870900, 1), (1, 2, 1669871000, 4)]),
+        ]);
+
+        let table = render_table(&leaderboard, "total", 2022, 1, &Settings::default()).unwrap();
+        let rows: Vec<_> = table.lines().collect();
+        assert!(rows[1].starts_with("*1.") && rows[1].contains("Alice"));
+        assert!(rows[2].contains("Bob"));
+
+        let table = render_table(&leaderboard, "part1", 2022, 1, &Settings::default()).unwrap();
+        let rows: Vec<_> = table.lines().collect();
+        assert!(rows[1].contains("Bob") && rows[2].contains("Alice"));
+    }
+
+    #[test]
+    fn test_rows_keep_times() {
+        // Alice finishes part 1 after 100 seconds and never gets part 2
+        let leaderboard = sample_board(vec![sample_member(1, "Alice", &[(1, 1, 1669870900, 1)])]);
+
+        let (rows, _) = day_rows(&leaderboard, "total", 2022, 1, &Settings::default());
+        assert_eq!(export_time(rows[0].part1), json!(100));
+        assert_eq!(export_time(rows[0].part2), Value::Null);
+        assert_eq!(rows[0].points2, None);
+    }
+}
@@ -0,0 +1,145 @@
+use std::error::Error;
+
+use chrono::Duration;
+use serde_json::{json, Value};
+
+use crate::{
+    cgi::{self, Request, Response},
+    query::Query,
+};
+
+/// A format the leaderboard tables can be downloaded in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    /// The format asked for with `?f=csv` or `?f=json`, if any
+    pub fn from_request(request: &Request) -> cgi::Result<Option<Self>> {
+        let query = request.parse_query()?;
+        match query.get_value("f") {
+            None => Ok(None),
+            Some("csv") => Ok(Some(Format::Csv)),
+            Some("json") => Ok(Some(Format::Json)),
+            Some(_) => Err(cgi::Error::response(Response::bad_request())),
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv",
+            Format::Json => "application/json",
+        }
+    }
+}
+
+/// Links to download the table as it's currently sorted
+pub fn render_downloads(query: &Query) -> String {
+    let mut csv_link = query.clone();
+    let mut json_link = query.clone();
+    csv_link.replace("f", "csv".into());
+    json_link.replace("f", "json".into());
+    format!("=> ?{csv_link} Download as CSV\n=> ?{json_link} Download as JSON")
+}
+
+/// A time as whole seconds, or null when it's missing
+pub fn export_time(time: Option<Duration>) -> Value {
+    match time {
+        Some(d) => json!(d.num_seconds()),
+        None => Value::Null,
+    }
+}
+
+/// The rows of a table, in the order they are shown
+pub struct Table {
+    columns: Vec<&'static str>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<&'static str>) -> Self {
+        Self {
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row, with a value for each column
+    pub fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    fn to_csv(&self) -> String {
+        let mut buffer = self.columns.join(",");
+        for row in &self.rows {
+            buffer += "\n";
+            buffer += &row.iter().map(csv_field).collect::<Vec<_>>().join(",");
+        }
+        buffer + "\n"
+    }
+
+    /// Every row as an object keyed by the column names, in the order of the columns
+    ///
+    /// A json `Map` would sort the keys, so the objects are written out by hand.
+    fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        let mut rows = Vec::new();
+        for row in &self.rows {
+            let mut fields = Vec::new();
+            for (column, value) in self.columns.iter().zip(row) {
+                fields.push(format!(
+                    "{}:{}",
+                    serde_json::to_string(column)?,
+                    serde_json::to_string(value)?
+                ));
+            }
+            rows.push(format!("{{{}}}", fields.join(",")));
+        }
+        Ok(format!("[{}]", rows.join(",")))
+    }
+
+    pub fn into_response(self, format: Format) -> Result<Response, Box<dyn Error>> {
+        let body = match format {
+            Format::Csv => self.to_csv(),
+            Format::Json => self.to_json()?,
+        };
+        Ok(Response::success(format.mime(), body))
+    }
+}
+
+/// A value as a csv field, quoted when it has to be
+fn csv_field(value: &Value) -> String {
+    let field = match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let mut table = Table::new(vec!["rank", "name", "time"]);
+        table.push(vec![json!(1), json!("Smith, \"Al\""), json!(3600)]);
+        table.push(vec![json!(2), json!("Bob"), Value::Null]);
+
+        assert_eq!(
+            table.to_csv(),
+            "rank,name,time\n1,\"Smith, \"\"Al\"\"\",3600\n2,Bob,\n"
+        );
+        assert_eq!(
+            table.to_json().unwrap(),
+            r#"[{"rank":1,"name":"Smith, \"Al\"","time":3600},{"rank":2,"name":"Bob","time":null}]"#
+        );
+    }
+}
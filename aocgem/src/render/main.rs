@@ -1,15 +1,20 @@
 use std::{cmp::Ordering, error::Error, time::SystemTime};
 
 use crate::{
-    cgi::{get_path, get_script, parse_query},
-    fetch::get_leaderboard,
-    leaderboard::{est_offset, Leaderboard, Member},
+    cgi::{Request, Response},
+    fetch::{get_leaderboard, Snapshot},
+    leaderboard::{est_from_timestamp, est_offset, Leaderboard, Member, Settings},
+    scoring::{scheme_scores, Scheme, Score},
 };
 
-use super::render_duration;
+use super::{
+    export::{export_time, render_downloads, Format, Table},
+    render_aoc_link, render_duration, render_stale, render_taken,
+};
 
 use ansi_term::{Color, Style};
-use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc};
+use serde_json::{json, Value};
 
 fn render_days(member: &Member, year: i32, render_color: bool) -> String {
     let times = member.completion_times(year);
@@ -18,10 +23,10 @@ fn render_days(member: &Member, year: i32, render_color: bool) -> String {
 
     for day in 0..25 {
         if let Some((a, b)) = times.get(&(day + 1)) {
-            if let Some(_) = a {
+            if a.is_some() {
                 foobar[day as usize] += 1;
             }
-            if let Some(_) = b {
+            if b.is_some() {
                 foobar[day as usize] += 1;
             }
         }
@@ -30,9 +35,9 @@ fn render_days(member: &Member, year: i32, render_color: bool) -> String {
     let mut buffer = String::new();
     let mut color = Style::default();
 
-    for day in 0..25 {
+    for stars in foobar {
         if render_color {
-            let next_color = match foobar[day] {
+            let next_color = match stars {
                 1 => Style::default(),
                 2 => Color::Yellow.normal(),
                 _ => Color::Fixed(8).blink(),
@@ -40,7 +45,7 @@ fn render_days(member: &Member, year: i32, render_color: bool) -> String {
             buffer += &color.infix(next_color).to_string();
             color = next_color;
         }
-        buffer += match foobar[day] {
+        buffer += match stars {
             1 => "+",
             2 => "*",
             _ => "-",
@@ -53,48 +58,89 @@ fn render_days(member: &Member, year: i32, render_color: bool) -> String {
     buffer
 }
 
-fn render_members(
-    leaderboard: &Leaderboard,
+fn render_score(score: &Score) -> String {
+    match score {
+        Score::Points(points) => points.to_string(),
+        Score::Time { days: 0, .. } => "--:--:--".to_owned(),
+        Score::Time { time, .. } => render_duration(time),
+    }
+}
+
+/// A missing time shows up as dashes in the table
+fn render_time(time: Option<Duration>) -> String {
+    match time {
+        Some(d) => render_duration(&d),
+        None => "--:--:--".to_owned(),
+    }
+}
+
+/// A score as points or whole seconds in the exports, or null before the first day is finished
+fn export_score(score: &Score) -> Value {
+    match score {
+        Score::Points(points) => json!(points),
+        Score::Time { days: 0, .. } => Value::Null,
+        Score::Time { time, .. } => json!(time.num_seconds()),
+    }
+}
+
+/// A member of the leaderboard table with the columns that need rendering
+struct MemberRow<'a> {
+    member: &'a Member,
+    days: String,
+    total_time: Option<Duration>,
+    average_time: Option<Duration>,
+    score: Score,
+    adjusted_time: Option<Duration>,
+    adjusted_average: Option<Duration>,
+}
+
+/// The members in the order of `sort_method`, shared by the table and the exports
+fn member_rows<'a>(
+    leaderboard: &'a Leaderboard,
     sort_method: &str,
     render_color: bool,
-) -> Result<String, Box<dyn Error>> {
-    let year = i32::from_str_radix(&leaderboard.event, 10)?;
+    settings: &Settings,
+) -> Result<Vec<MemberRow<'a>>, Box<dyn Error>> {
+    let year = leaderboard.event.parse::<i32>()?;
     let mut elements = Vec::new();
+    let scores = scheme_scores(leaderboard, year, settings.scheme);
 
-    for (_, member) in &leaderboard.members {
+    for member in leaderboard.members.values() {
         let total_time = member.total_completion_time(year);
         let average_time = match member.stars == 0 {
             true => total_time,
-            false => match total_time {
-                Some(t) => Some(t / member.stars),
-                None => None,
-            },
+            false => total_time.map(|t| t / member.stars),
+        };
+        let start = settings.start(member.id);
+        let adjusted_time = member.total_adjusted_time(year, start);
+        let adjusted_average = match member.stars == 0 {
+            true => adjusted_time,
+            false => adjusted_time.map(|t| t / member.stars),
         };
         elements.push((
             // Sorting elements
-            member.local_score,
-            average_time.map(|t| t.num_seconds()),
-            member.global_score,
-            member.stars,
+            (
+                member.local_score,
+                average_time.map(|t| t.num_seconds()),
+                member.global_score,
+                member.stars,
+                scores[&member.id].key(),
+            ),
             // Data
-            member.local_score.to_string(),
-            member.global_score.to_string(),
-            member.stars.to_string(),
-            render_days(member, year, render_color),
-            match total_time {
-                Some(d) => render_duration(&d),
-                None => "--:--:--".to_owned(),
-            },
-            match average_time {
-                Some(d) => render_duration(&d),
-                None => "--:--:--".to_owned(),
+            MemberRow {
+                member,
+                days: render_days(member, year, render_color),
+                total_time,
+                average_time,
+                score: scores[&member.id].clone(),
+                adjusted_time,
+                adjusted_average,
             },
-            &member.name,
         ));
     }
 
     match sort_method {
-        "stars" => elements.sort_unstable_by(|a, b| match a.3.cmp(&b.3) {
+        "stars" => elements.sort_unstable_by(|(a, _), (b, _)| match a.3.cmp(&b.3) {
             Ordering::Equal => match a.0.cmp(&b.0) {
                 Ordering::Equal => a.1.cmp(&b.1),
                 Ordering::Less => Ordering::Greater,
@@ -103,7 +149,7 @@ fn render_members(
             Ordering::Less => Ordering::Greater,
             Ordering::Greater => Ordering::Less,
         }),
-        "global" => elements.sort_unstable_by(|a, b| match a.2.cmp(&b.2) {
+        "global" => elements.sort_unstable_by(|(a, _), (b, _)| match a.2.cmp(&b.2) {
             Ordering::Equal => match a.0.cmp(&b.0) {
                 Ordering::Equal => a.1.cmp(&b.1),
                 Ordering::Less => Ordering::Greater,
@@ -112,29 +158,46 @@ fn render_members(
             Ordering::Less => Ordering::Greater,
             Ordering::Greater => Ordering::Less,
         }),
-        "time" => elements.sort_unstable_by(|a, b| a.1.cmp(&b.1)),
-        _ => elements.sort_unstable_by(|a, b| match a.0.cmp(&b.0) {
+        "time" => elements.sort_unstable_by_key(|(a, _)| a.1),
+        "scheme" => elements.sort_unstable_by(|(a, _), (b, _)| match a.4.cmp(&b.4) {
+            Ordering::Equal => b.0.cmp(&a.0),
+            ordering => ordering,
+        }),
+        _ => elements.sort_unstable_by(|(a, _), (b, _)| match a.0.cmp(&b.0) {
             Ordering::Equal => a.1.cmp(&b.1),
             Ordering::Less => Ordering::Greater,
             Ordering::Greater => Ordering::Less,
         }),
     };
 
+    Ok(elements.into_iter().map(|(_, row)| row).collect())
+}
+
+pub fn render_members(
+    leaderboard: &Leaderboard,
+    sort_method: &str,
+    render_color: bool,
+    settings: &Settings,
+) -> Result<String, Box<dyn Error>> {
+    let scheme = settings.scheme;
+    let rows = member_rows(leaderboard, sort_method, render_color, settings)?;
+
     let mut local_w = 0;
     let mut global_w = 0;
     let mut stars_w = 0;
     let mut dur_w = 0;
     let mut avg_w = 0;
     let mut name_w = 0;
-    for (_, _, _, _, local, global, stars, _days, dur, avg, name) in &elements {
-        local_w = local.len().max(local_w);
-        global_w = global.len().max(global_w);
-        stars_w = stars.len().max(stars_w);
-        dur_w = dur.len().max(dur_w);
-        avg_w = avg.len().max(avg_w);
-        name_w = name.len().max(name_w);
+    let mut scheme_w = 0;
+    for row in &rows {
+        scheme_w = render_score(&row.score).len().max(scheme_w);
+        local_w = row.member.local_score.to_string().len().max(local_w);
+        global_w = row.member.global_score.to_string().len().max(global_w);
+        stars_w = row.member.stars.to_string().len().max(stars_w);
+        dur_w = render_time(row.total_time).len().max(dur_w);
+        avg_w = render_time(row.average_time).len().max(avg_w);
+        name_w = row.member.name.len().max(name_w);
     }
-
     let mut buffer = String::new();
 
     let n_t = "";
@@ -144,6 +207,11 @@ fn render_members(
     let dur_t = "Total Time";
     let avg_t = "Average";
     let name_t = "Name";
+    let scheme_t = match scheme {
+        Scheme::Aoc => "",
+        Scheme::Delta => "Delta",
+        Scheme::Median => "Median",
+    };
 
     local_w = local_w.max(score_t.len() / 2);
     global_w = global_w.max(score_t.len() / 2);
@@ -151,40 +219,198 @@ fn render_members(
     dur_w = dur_w.max(dur_t.len());
     avg_w = avg_w.max(avg_t.len());
     name_w = name_w.max(name_t.len());
+    // The scheme only gets a column when it isn't the local score
+    let scheme_w = match scheme {
+        Scheme::Aoc => 0,
+        _ => scheme_w.max(scheme_t.len()) + 1,
+    };
 
-    let n_w = elements.len().to_string().len() + 1;
+    let n_w = rows.len().to_string().len() + 1;
     let score_w = local_w + global_w + 1;
-    let pre_w = n_w + score_w + stars_w + 2;
+    let pre_w = n_w + scheme_w + score_w + stars_w + 2;
     buffer += &format!("{n_t:<pre_w$}          1111111111222222");
     buffer += &format!(
-        "\n{n_t:<n_w$} {score_t:^score_w$} {stars_t:<stars_w$} {days_t} {dur_t:<dur_w$} {avg_t:<avg_w$} {name_t:<name_w$}"
+        "\n{n_t:<n_w$} {scheme_t:<scheme_w$}{score_t:^score_w$} {stars_t:<stars_w$} {days_t} {dur_t:<dur_w$} {avg_t:<avg_w$} {name_t:<name_w$}"
     );
 
-    for (i, (_, _, _, _, local, global, stars, days, dur, avg, name)) in
-        elements.into_iter().enumerate()
-    {
+    // Adjusted times are only shown once someone has set when they start
+    let adjusted = !settings.starts.is_empty();
+    if adjusted {
+        buffer += &format!(" {:<8} {:<8}", "Adjusted", "Adj. Avg");
+    }
+
+    for (i, row) in rows.into_iter().enumerate() {
         let i = i + 1;
         let i = format!("{i}.");
-        buffer += &format!("\n{i:>n_w$} {local:>local_w$}:{global:<global_w$} {stars:<stars_w$} {days} {dur:>dur_w$} {avg:>avg_w$} {name:<name_w$}");
+        let score = match scheme {
+            Scheme::Aoc => String::new(),
+            _ => format!("{} ", render_score(&row.score)),
+        };
+        let local = row.member.local_score;
+        let global = row.member.global_score;
+        let stars = row.member.stars;
+        let days = row.days;
+        let dur = render_time(row.total_time);
+        let avg = render_time(row.average_time);
+        let name = &row.member.name;
+        let adj = render_time(row.adjusted_time);
+        let adj_avg = render_time(row.adjusted_average);
+        buffer += &format!("\n{i:>n_w$} {score:>scheme_w$}{local:>local_w$}:{global:<global_w$} {stars:<stars_w$} {days} {dur:>dur_w$} {avg:>avg_w$} {name:<name_w$}");
+        if adjusted {
+            buffer += &format!(" {adj:>8} {adj_avg:>8}");
+        }
     }
 
     Ok(buffer)
 }
 
-pub fn render_leaderboard(session: &str, year: i32, id: &str) -> Result<String, Box<dyn Error>> {
-    let leaderboard = get_leaderboard(session, year, id)?;
+/// The sort used when the query doesn't pick one
+fn default_sort(scheme: Scheme) -> &'static str {
+    match scheme {
+        Scheme::Aoc => "local",
+        _ => "scheme",
+    }
+}
 
-    let query = parse_query()?;
-    let sort_method = query.get_value("s").unwrap_or("local");
+/// Export the leaderboard table in the same order as it is shown
+pub fn export_leaderboard(
+    request: &Request,
+    session: &str,
+    year: i32,
+    id: &str,
+    settings: &Settings,
+    format: Format,
+) -> Result<Response, Box<dyn Error>> {
+    let leaderboard = get_leaderboard(session, year, id)?.leaderboard;
+    export_board(request, &leaderboard, settings, format)
+}
+
+/// Export the leaderboard table as it was when the snapshot was taken
+pub fn export_snapshot(
+    request: &Request,
+    snapshot: &Snapshot,
+    settings: &Settings,
+    format: Format,
+) -> Result<Response, Box<dyn Error>> {
+    export_board(request, &snapshot.leaderboard, settings, format)
+}
+
+/// The leaderboard table as a csv or json download
+fn export_board(
+    request: &Request,
+    leaderboard: &Leaderboard,
+    settings: &Settings,
+    format: Format,
+) -> Result<Response, Box<dyn Error>> {
+    let query = request.parse_query()?;
+    let sort_method = query
+        .get_value("s")
+        .unwrap_or(default_sort(settings.scheme));
+    let rows = member_rows(leaderboard, sort_method, false, settings)?;
+    let mut table = Table::new(vec![
+        "rank",
+        "id",
+        "name",
+        "local_score",
+        "global_score",
+        "stars",
+        "days",
+        "total_time",
+        "average_time",
+        "score",
+        "adjusted_time",
+        "adjusted_average",
+    ]);
+    for (i, row) in rows.into_iter().enumerate() {
+        let member = row.member;
+        table.push(vec![
+            json!(i + 1),
+            json!(member.id),
+            json!(member.name),
+            json!(member.local_score),
+            json!(member.global_score),
+            json!(member.stars),
+            json!(row.days),
+            export_time(row.total_time),
+            export_time(row.average_time),
+            export_score(&row.score),
+            export_time(row.adjusted_time),
+            export_time(row.adjusted_average),
+        ]);
+    }
+    table.into_response(format)
+}
+
+pub fn render_leaderboard(
+    request: &Request,
+    session: &str,
+    year: i32,
+    id: &str,
+    settings: &Settings,
+) -> Result<String, Box<dyn Error>> {
+    let fetched = get_leaderboard(session, year, id)?;
+    let stale = render_stale(&fetched.stale);
+
+    let now = DateTime::<Utc>::from(SystemTime::now());
+    let now = DateTime::<FixedOffset>::from_utc(now.naive_utc(), est_offset());
+    render_board(
+        request,
+        &fetched.leaderboard,
+        year,
+        id,
+        &stale,
+        now,
+        settings,
+    )
+}
+
+/// Render the leaderboard as it was when the snapshot was taken
+pub fn render_snapshot(
+    request: &Request,
+    snapshot: &Snapshot,
+    year: i32,
+    id: &str,
+    settings: &Settings,
+) -> Result<String, Box<dyn Error>> {
+    let taken = render_taken(snapshot.taken);
+    let now = est_from_timestamp(snapshot.taken);
+    render_board(
+        request,
+        &snapshot.leaderboard,
+        year,
+        id,
+        &taken,
+        now,
+        settings,
+    )
+}
+
+/// Render the leaderboard with a notice below the adventofcode.com link
+///
+/// `now` decides which day is linked as the latest.
+fn render_board(
+    request: &Request,
+    leaderboard: &Leaderboard,
+    year: i32,
+    id: &str,
+    notice: &str,
+    now: DateTime<FixedOffset>,
+    settings: &Settings,
+) -> Result<String, Box<dyn Error>> {
+    let scheme = settings.scheme;
+    let query = request.parse_query()?;
+    let sort_method = query.get_value("s").unwrap_or(default_sort(scheme));
     let render_color = query.contains("c");
 
-    let scores = render_members(&leaderboard, &sort_method, render_color)?;
+    let scores = render_members(leaderboard, sort_method, render_color, settings)?;
 
     let mut global_link = query.clone();
     let mut local_link = query.clone();
     let mut stars_link = query.clone();
     let mut time_link = query.clone();
+    let mut scheme_link = query.clone();
 
+    scheme_link.replace("s", "scheme".into());
     global_link.replace("s", "global".into());
     local_link.replace("s", "local".into());
     stars_link.replace("s", "stars".into());
@@ -194,10 +420,11 @@ pub fn render_leaderboard(session: &str, year: i32, id: &str) -> Result<String,
         "stars" => "stars",
         "global" => "global score",
         "time" => "time",
+        "scheme" => scheme.name(),
         _ => "local score",
     };
 
-    let sort_options = match sort_method.as_ref() {
+    let mut sort_options = match sort_method {
         "stars" => format!(
             "=> ?{global_link} Sort by global score
 => ?{local_link} Sort by local score
@@ -212,6 +439,12 @@ pub fn render_leaderboard(session: &str, year: i32, id: &str) -> Result<String,
             "=> ?{global_link} Sort by global score
 => ?{local_link} Sort by local score
 => ?{stars_link} Sort by stars"
+        ),
+        "scheme" => format!(
+            "=> ?{global_link} Sort by global score
+=> ?{local_link} Sort by local score
+=> ?{stars_link} Sort by stars
+=> ?{time_link} Sort by time"
         ),
         _ => format!(
             "=> ?{global_link} Sort by global score
@@ -220,6 +453,10 @@ pub fn render_leaderboard(session: &str, year: i32, id: &str) -> Result<String,
         ),
     };
 
+    if scheme != Scheme::Aoc && sort_method != "scheme" {
+        sort_options += &format!("\n=> ?{scheme_link} Sort by {}", scheme.name());
+    }
+
     let mut color_select = query.clone();
     let color_name = if render_color {
         color_select.erase("c");
@@ -232,8 +469,6 @@ pub fn render_leaderboard(session: &str, year: i32, id: &str) -> Result<String,
         "Enable Colors"
     };
 
-    let now = DateTime::<Utc>::from(SystemTime::now());
-    let now = DateTime::<FixedOffset>::from_utc(now.naive_utc(), est_offset());
     let today = now.date_naive();
 
     let latest = NaiveDate::from_ymd_opt(year, 12, 25).unwrap();
@@ -242,11 +477,24 @@ pub fn render_leaderboard(session: &str, year: i32, id: &str) -> Result<String,
         false => today.day(),
     };
 
-    let script = get_script();
-    let path = get_path();
-    Ok(format!("
-=> https://adventofcode.com/{year}/leaderboard/private/view/{id} View the leaderboard on adventofcode.com
+    let script = request.script();
+    let path = request.path();
+
+    let mut members: Vec<_> = leaderboard.members.values().collect();
+    members.sort_unstable_by_key(|member| member.name.to_lowercase());
+    let members = members
+        .into_iter()
+        .map(|member| format!("=> {script}{path}/member/{}/ {}", member.id, member.name))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let downloads = render_downloads(&query);
 
+    let aoc_link = render_aoc_link(year, id);
+    Ok(format!(
+        "
+{aoc_link}
+{notice}
 > Sorting by {sort_name}
 
 ```leaderboard table
@@ -257,6 +505,8 @@ pub fn render_leaderboard(session: &str, year: i32, id: &str) -> Result<String,
 
 {sort_options}
 
+{downloads}
+
 ## View times for specific days
 
 You can view statistics for a specific day's problem.
@@ -264,5 +514,12 @@ You can view statistics for a specific day's problem.
 => {script}{path}/{latest_day}/ View day {latest_day}'s stats
 => {script}{path}/day/ Select a day to view
 
-"))
+## Members
+
+View each member's days, ranks and personal bests.
+
+{members}
+
+"
+    ))
 }
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use chrono::Duration;
+
+use crate::{
+    leaderboard::Leaderboard,
+    scoring::{local_scores, median, points, ranks},
+};
+
+use super::{render_difference, render_duration};
+
+fn render_time(time: &Option<Duration>) -> String {
+    match time {
+        Some(d) => render_duration(d),
+        None => "--:--:--".to_owned(),
+    }
+}
+
+/// The rank of the member for a part of the day, from the local score points they earned
+fn day_rank(count: i32, points: &HashMap<(u32, u32), i32>, day: u32, part: u32) -> Option<i32> {
+    points.get(&(day, part)).map(|points| count + 1 - points)
+}
+
+/// The day and time of the fastest time in `times`
+fn best(times: impl Iterator<Item = (u32, Option<Duration>)>) -> String {
+    match times.filter_map(|(day, time)| Some((time?, day))).min() {
+        Some((time, day)) => format!("{} on day {day}", render_duration(&time)),
+        None => "none yet".to_owned(),
+    }
+}
+
+/// Render every day of a member's year compared to the rest of the leaderboard
+///
+/// Returns `None` when the member isn't part of the leaderboard.
+pub fn render_member(leaderboard: &Leaderboard, year: i32, id: &str) -> Option<String> {
+    let member = leaderboard.members.get(id)?;
+    let count = leaderboard.members.len() as i32;
+    let points = points(leaderboard, i64::MAX);
+    let points = &points[&member.id];
+
+    // The median time to finish each day, for everyone who finished it
+    let mut finishes: HashMap<u32, Vec<Duration>> = HashMap::new();
+    for other in leaderboard.members.values() {
+        for (day, times) in other.completion_times(year) {
+            if let (Some(a), Some(b)) = times {
+                finishes.entry(day).or_default().push(a + b);
+            }
+        }
+    }
+    let medians: HashMap<u32, Duration> = finishes
+        .into_iter()
+        .map(|(day, times)| (day, median(times)))
+        .collect();
+
+    let mut days: Vec<_> = member.completion_times(year).into_iter().collect();
+    days.sort_unstable_by_key(|(day, _)| *day);
+
+    let mut rows = Vec::new();
+    for (day, (part1, delta)) in &days {
+        let finish = part1.and_then(|a| Some(a + (*delta)?));
+        let rank1 = day_rank(count, points, *day, 1);
+        let rank2 = day_rank(count, points, *day, 2);
+        let compared = match (finish, medians.get(day)) {
+            (Some(finish), Some(median)) => render_difference(&(finish - *median)),
+            _ => "--".to_owned(),
+        };
+        rows.push((
+            day.to_string(),
+            render_time(part1),
+            rank1.map_or("-".to_owned(), |rank| rank.to_string()),
+            render_time(&finish),
+            render_time(delta),
+            rank2.map_or("-".to_owned(), |rank| rank.to_string()),
+            medians
+                .get(day)
+                .map_or("--:--:--".to_owned(), render_duration),
+            compared,
+        ));
+    }
+
+    let day_t = "Day";
+    let part1_t = "Part 1";
+    let part2_t = "Part 2";
+    let delta_t = "Delta";
+    let rank_t = "Rank";
+    let median_t = "Median";
+    let compared_t = "vs Median";
+
+    let mut compared_w = compared_t.len();
+    for (_, _, _, _, _, _, _, compared) in &rows {
+        compared_w = compared.len().max(compared_w);
+    }
+
+    let mut table = format!(
+        "{day_t:<3} {part1_t:<8} {rank_t:>4} {part2_t:<8} {delta_t:<8} {rank_t:>4} {median_t:<8} {compared_t:<compared_w$}"
+    );
+    for (day, part1, rank1, part2, delta, rank2, median, compared) in rows {
+        table += &format!(
+            "\n{day:>3} {part1:>8} {rank1:>4} {part2:>8} {delta:>8} {rank2:>4} {median:>8} {compared:>compared_w$}"
+        );
+    }
+
+    let rank = ranks(leaderboard, i64::MAX)
+        .into_iter()
+        .position(|other| other == member.id)
+        .unwrap_or(0)
+        + 1;
+    let local_score = local_scores(leaderboard, i64::MAX)[&member.id];
+    let stars = member.stars;
+
+    let best_part1 = best(days.iter().map(|(day, (a, _))| (*day, *a)));
+    let best_part2 = best(
+        days.iter()
+            .map(|(day, (a, b))| (*day, a.and_then(|a| Some(a + (*b)?)))),
+    );
+    let best_delta = best(days.iter().map(|(day, (_, b))| (*day, *b)));
+    let best_rank = match days
+        .iter()
+        .filter_map(|(day, _)| Some((day_rank(count, points, *day, 2)?, *day)))
+        .min()
+    {
+        Some((rank, day)) => format!("{rank} on day {day}"),
+        None => "none yet".to_owned(),
+    };
+
+    Some(format!(
+        "
+Rank {rank} of {count} with a local score of {local_score} and {stars} stars.
+
+```member days
+{table}
+```
+
+Part 2 is the time since the puzzle unlocked, and the delta is the time between both stars. The median is the median time to finish both parts of the day on the leaderboard.
+
+## Personal bests
+
+* Fastest part 1: {best_part1}
+* Fastest part 2: {best_part2}
+* Fastest delta: {best_delta}
+* Best part 2 rank: {best_rank}
+"
+    ))
+}
+
+/// Render the member's results in every year they were part of the leaderboard
+///
+/// `years` has to be in order. Returns `None` when the member isn't part of any of them.
+pub fn render_member_years(years: &[(i32, Leaderboard)], id: &str) -> Option<String> {
+    let year_t = "Year";
+    let rank_t = "Rank";
+    let score_t = "Score";
+    let stars_t = "Stars";
+    let avg_t = "Average";
+
+    let mut rows = Vec::new();
+    for (year, leaderboard) in years {
+        let member = match leaderboard.members.get(id) {
+            Some(member) => member,
+            None => continue,
+        };
+        let rank = ranks(leaderboard, i64::MAX)
+            .into_iter()
+            .position(|other| other == member.id)
+            .unwrap_or(0)
+            + 1;
+        let average = match member.stars {
+            0 => None,
+            stars => member.total_completion_time(*year).map(|t| t / stars),
+        };
+        rows.push((
+            year,
+            format!("{rank}/{}", leaderboard.members.len()),
+            local_scores(leaderboard, i64::MAX)[&member.id],
+            member.stars,
+            render_time(&average),
+        ));
+    }
+    if rows.is_empty() {
+        return None;
+    }
+
+    let rank_w = rows
+        .iter()
+        .map(|(_, rank, _, _, _)| rank.len())
+        .max()
+        .unwrap_or(0)
+        .max(rank_t.len());
+    let mut table = format!("{year_t:<4} {rank_t:<rank_w$} {score_t:<5} {stars_t:<5} {avg_t}");
+    for (year, rank, score, stars, average) in rows {
+        table += &format!("\n{year:<4} {rank:>rank_w$} {score:>5} {stars:>5} {average:>8}");
+    }
+    Some(table)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::leaderboard::test::{sample_board, sample_member};
+
+    #[test]
+    fn test_render_member() {
+        // Dec 1 2022 unlocked at 1669870800
+        let leaderboard = sample_board(vec![
+            sample_member(1, "Alice", &[(1, 1, 1669870900, 1), (1, 2, 1669871800, 3)]),
+            sample_member(2, "Bob", &[(1, 1, 1669871000, 2), (1, 2, 1669871200, 4)]),
+        ]);
+
+        let alice = render_member(&leaderboard, 2022, "1").unwrap();
+        // First for part 1 and second for part 2, 300s slower than the median finish
+        assert!(alice.contains("  1 00:01:40    1 00:16:40 00:15:00    2 00:11:40 +00:05:00"));
+        assert!(alice.contains("Fastest part 1: 00:01:40 on day 1"));
+        assert!(alice.contains("Best part 2 rank: 2 on day 1"));
+        assert!(render_member(&leaderboard, 2022, "3").is_none());
+    }
+
+    #[test]
+    fn test_render_member_years() {
+        let years = vec![
+            (
+                2021,
+                sample_board(vec![sample_member(2, "Bob", &[(1, 1, 1638334900, 1)])]),
+            ),
+            (
+                2022,
+                sample_board(vec![
+                    sample_member(1, "Alice", &[(1, 1, 1669870900, 1)]),
+                    sample_member(2, "Bob", &[(1, 1, 1669871000, 2)]),
+                ]),
+            ),
+        ];
+
+        // Alice is only part of the leaderboard in 2022, where she beat Bob
+        let alice = render_member_years(&years, "1").unwrap();
+        assert_eq!(alice.lines().count(), 2);
+        assert!(alice.contains("\n2022  1/2     2     1 00:01:40"));
+        let bob = render_member_years(&years, "2").unwrap();
+        assert!(bob.contains("\n2021  1/1     1     1 00:01:40"));
+        assert!(bob.contains("\n2022  2/2     1     1 00:03:20"));
+        assert!(render_member_years(&years, "3").is_none());
+    }
+}
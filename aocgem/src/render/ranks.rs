@@ -0,0 +1,192 @@
+use std::{collections::HashMap, error::Error, time::SystemTime};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{
+    fetch::get_leaderboard,
+    leaderboard::{est_midnight, Leaderboard},
+    scoring::ranks,
+};
+
+use super::{render_aoc_link, render_stale};
+
+/// How many members are shown in the chart and the overtakes
+const TOP: usize = 10;
+
+/// The rank of every member at the end of each day that has unlocked
+fn daily_ranks(leaderboard: &Leaderboard, year: i32, now: i64) -> Vec<HashMap<i32, usize>> {
+    let mut days = Vec::new();
+    for day in 1..=25 {
+        let start = est_midnight(NaiveDate::from_ymd_opt(year, 12, day).unwrap()).timestamp();
+        if start > now {
+            break;
+        }
+        let end = (start + 24 * 3600).min(now);
+        days.push(
+            ranks(leaderboard, end)
+                .into_iter()
+                .enumerate()
+                .map(|(rank, id)| (id, rank))
+                .collect(),
+        );
+    }
+    days
+}
+
+fn render_table(names: &HashMap<i32, &str>, days: &[HashMap<i32, usize>], order: &[i32]) -> String {
+    // Wide enough for the day numbers in the header
+    let rank_w = (order.len().to_string().len() + 1).max(3);
+    let n_w = order.len().to_string().len() + 1;
+    let name_t = "Name";
+    let name_w = order
+        .iter()
+        .map(|id| names[id].len())
+        .max()
+        .unwrap_or(0)
+        .max(name_t.len());
+
+    let n_t = "";
+    let mut buffer = format!("{n_t:<n_w$} {name_t:<name_w$}");
+    for day in 1..=days.len() {
+        buffer += &format!("{day:>rank_w$}");
+    }
+
+    for (i, id) in order.iter().enumerate() {
+        let i = format!("{}.", i + 1);
+        let name = names[id];
+        buffer += &format!("\n{i:>n_w$} {name:<name_w$}");
+        for ranks in days {
+            let rank = ranks[id] + 1;
+            buffer += &format!("{rank:>rank_w$}");
+        }
+    }
+    buffer
+}
+
+/// Plot the rank of the top members for each day
+///
+/// Every member is marked with a letter, and a line is drawn while they keep
+/// the same rank.
+fn render_chart(days: &[HashMap<i32, usize>], letters: &HashMap<i32, char>) -> String {
+    let rows = letters.len();
+    let label_w = rows.to_string().len();
+    let mut grid = vec![vec![' '; days.len() * 3]; rows];
+
+    for (&id, &letter) in letters {
+        for (day, ranks) in days.iter().enumerate() {
+            let rank = ranks[&id];
+            if rank >= rows {
+                continue;
+            }
+            grid[rank][day * 3 + 1] = letter;
+            if days.get(day + 1).is_some_and(|next| next[&id] == rank) {
+                grid[rank][day * 3 + 2] = '-';
+                grid[rank][day * 3 + 3] = '-';
+            }
+        }
+    }
+
+    let mut buffer = String::new();
+    for (rank, row) in grid.into_iter().enumerate() {
+        let rank = rank + 1;
+        let row: String = row.into_iter().collect();
+        buffer += &format!("{rank:>label_w$} |{}\n", row.trim_end());
+    }
+    let empty = "";
+    buffer += &format!("{empty:>label_w$} +{}\n", "-".repeat(days.len() * 3));
+    buffer += &format!("{empty:>label_w$}  ");
+    for day in 1..=days.len() {
+        buffer += &format!("{day:^3}");
+    }
+    buffer.trim_end().to_owned()
+}
+
+/// List every time a member passed someone else in the top members
+fn render_overtakes(
+    names: &HashMap<i32, &str>,
+    days: &[HashMap<i32, usize>],
+    order: &[i32],
+) -> String {
+    let mut buffer = String::new();
+    for (day, pair) in days.windows(2).enumerate() {
+        let (before, after) = (&pair[0], &pair[1]);
+        let mut passed: Vec<_> = order
+            .iter()
+            .flat_map(|a| order.iter().map(move |b| (a, b)))
+            .filter(|(a, b)| after[*b] < TOP && before[*a] > before[*b] && after[*a] < after[*b])
+            .collect();
+        passed.sort_unstable_by_key(|(a, b)| (after[*a], after[*b]));
+        for (a, b) in passed {
+            let (a, b) = (names[a], names[b]);
+            buffer += &format!("* Day {}: {a} overtook {b}\n", day + 2);
+        }
+    }
+    if buffer.is_empty() {
+        "Nobody has overtaken anyone yet.".to_owned()
+    } else {
+        buffer.trim_end().to_owned()
+    }
+}
+
+pub fn render_ranks(session: &str, year: i32, id: &str) -> Result<String, Box<dyn Error>> {
+    let fetched = get_leaderboard(session, year, id)?;
+    let leaderboard = &fetched.leaderboard;
+    let stale = render_stale(&fetched.stale);
+
+    let now = DateTime::<Utc>::from(SystemTime::now()).timestamp();
+    let days = daily_ranks(leaderboard, year, now);
+    let last = match days.last() {
+        Some(last) => last,
+        None => return Ok(format!("{stale}\nThe event hasn't started yet.")),
+    };
+
+    let names: HashMap<i32, &str> = leaderboard
+        .members
+        .values()
+        .map(|member| (member.id, member.name.as_str()))
+        .collect();
+    let mut order: Vec<i32> = names.keys().copied().collect();
+    order.sort_unstable_by_key(|id| last[id]);
+
+    let letters: HashMap<i32, char> = order
+        .iter()
+        .take(TOP)
+        .zip(('A'..='Z').chain('a'..='z'))
+        .map(|(id, letter)| (*id, letter))
+        .collect();
+    let legend = order
+        .iter()
+        .take(TOP)
+        .map(|id| format!("{} {}", letters[id], names[id]))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let table = render_table(&names, &days, &order);
+    let chart = render_chart(&days, &letters);
+    let overtakes = render_overtakes(&names, &days, &order);
+
+    let aoc_link = render_aoc_link(year, id);
+    Ok(format!(
+        "
+{aoc_link}
+{stale}
+Each member's rank at the end of every day.
+
+```rank table
+{table}
+```
+
+## Top {TOP}
+
+```rank chart
+{chart}
+
+{legend}
+```
+
+## Overtakes
+
+{overtakes}
+"
+    ))
+}
@@ -0,0 +1,233 @@
+use std::{collections::HashMap, error::Error};
+
+use chrono::Duration;
+use urlencoding::encode;
+
+use crate::{
+    fetch::get_leaderboard,
+    leaderboard::{Leaderboard, Settings},
+};
+
+use super::{main::render_members, render_aoc_link, render_duration, render_stale};
+
+/// The combined results of a team's members
+struct TeamScore<'a> {
+    name: &'a str,
+    local_score: i32,
+    stars: i32,
+    /// The time for all of the team's stars
+    total_time: Option<Duration>,
+    /// How many of the team's members are on the leaderboard
+    members: usize,
+}
+
+impl TeamScore<'_> {
+    /// The average time for each star, like a member's average
+    fn average_time(&self) -> Option<Duration> {
+        match self.stars {
+            0 => self.total_time,
+            stars => self.total_time.map(|t| t / stars),
+        }
+    }
+}
+
+/// Add up the members of every team, the best team first
+fn team_scores<'a>(
+    leaderboard: &Leaderboard,
+    year: i32,
+    teams: &'a HashMap<String, Vec<i32>>,
+) -> Vec<TeamScore<'a>> {
+    let mut scores: Vec<_> = teams
+        .iter()
+        .map(|(name, ids)| {
+            let members: Vec<_> = leaderboard
+                .members
+                .values()
+                .filter(|member| ids.contains(&member.id))
+                .collect();
+            TeamScore {
+                name,
+                local_score: members.iter().map(|member| member.local_score).sum(),
+                stars: members.iter().map(|member| member.stars).sum(),
+                total_time: members
+                    .iter()
+                    .filter_map(|member| member.total_completion_time(year))
+                    .reduce(|total, time| total + time),
+                members: members.len(),
+            }
+        })
+        .collect();
+    scores.sort_unstable_by(|a, b| {
+        b.local_score
+            .cmp(&a.local_score)
+            .then_with(|| a.average_time().cmp(&b.average_time()))
+            .then_with(|| a.name.cmp(b.name))
+    });
+    scores
+}
+
+fn render_scores(scores: &[TeamScore]) -> String {
+    let n_t = "";
+    let score_t = "Score";
+    let stars_t = "Stars";
+    let avg_t = "Average";
+    let members_t = "Members";
+    let name_t = "Team";
+
+    let n_w = scores.len().to_string().len() + 1;
+    let score_w = scores
+        .iter()
+        .map(|team| team.local_score.to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max(score_t.len());
+    let stars_w = scores
+        .iter()
+        .map(|team| team.stars.to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max(stars_t.len());
+
+    let mut buffer = format!(
+        "{n_t:<n_w$} {score_t:<score_w$} {stars_t:<stars_w$} {avg_t:<8} {members_t} {name_t}"
+    );
+    for (i, team) in scores.iter().enumerate() {
+        let i = format!("{}.", i + 1);
+        let score = team.local_score;
+        let stars = team.stars;
+        let avg = match team.average_time() {
+            Some(d) => render_duration(&d),
+            None => "--:--:--".to_owned(),
+        };
+        let members = team.members;
+        let name = team.name;
+        buffer += &format!(
+            "\n{i:>n_w$} {score:>score_w$} {stars:>stars_w$} {avg:>8} {members:>7} {name}"
+        );
+    }
+    buffer
+}
+
+/// Render the standings of every team, `base` is the path of the teams page
+pub fn render_teams(
+    session: &str,
+    year: i32,
+    id: &str,
+    teams: &HashMap<String, Vec<i32>>,
+    base: &str,
+) -> Result<String, Box<dyn Error>> {
+    let fetched = get_leaderboard(session, year, id)?;
+    let leaderboard = &fetched.leaderboard;
+    let stale = render_stale(&fetched.stale);
+    let aoc_link = render_aoc_link(year, id);
+
+    let scores = team_scores(leaderboard, year, teams);
+    let table = match scores.is_empty() {
+        true => "There are no teams yet.".to_owned(),
+        false => format!("```team table\n{}\n```", render_scores(&scores)),
+    };
+    let links = scores
+        .iter()
+        .map(|team| format!("=> {base}/{}/ View team {}", encode(team.name), team.name))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // Everyone's id, since adventofcode.com doesn't show them
+    let mut members: Vec<_> = leaderboard.members.values().collect();
+    members.sort_unstable_by_key(|member| member.name.to_lowercase());
+    let name_w = members
+        .iter()
+        .map(|member| member.name.len())
+        .max()
+        .unwrap_or(0);
+    let ids = members
+        .iter()
+        .map(|member| format!("{:<name_w$} {}", member.name, member.id))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(
+        "
+{aoc_link}
+{stale}
+The local score and stars of every member of a team added up. The average is the team's average time for each star.
+
+{table}
+
+{links}
+
+## Change the teams
+
+=> {base}/edit/ Add or change a team
+
+Only the owner of the leaderboard can change the teams, with a client certificate linked to the leaderboard's session. Enter the team's name and the ids of its members, like `Backend: 123, 456`. A team without any ids is removed.
+
+```member ids
+{ids}
+```
+"
+    ))
+}
+
+/// Render the members of a team, the same way as the leaderboard
+pub fn render_team(
+    session: &str,
+    year: i32,
+    id: &str,
+    team: &[i32],
+    settings: &Settings,
+) -> Result<String, Box<dyn Error>> {
+    let fetched = get_leaderboard(session, year, id)?;
+    let mut leaderboard = fetched.leaderboard;
+    let stale = render_stale(&fetched.stale);
+    let aoc_link = render_aoc_link(year, id);
+
+    leaderboard
+        .members
+        .retain(|_, member| team.contains(&member.id));
+    let members = render_members(&leaderboard, "local", false, settings)?;
+
+    Ok(format!(
+        "
+{aoc_link}
+{stale}
+```team members
+{members}
+```
+"
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::leaderboard::test::{sample_board, sample_member};
+
+    #[test]
+    fn test_team_scores() {
+        // Dec 1 2022 unlocked at 1669870800
+        let member = |id: i32, name: &str, ts: i64, local_score: i32| {
+            let mut member = sample_member(id, name, &[(1, 1, ts, id)]);
+            member.local_score = local_score;
+            member
+        };
+        let leaderboard = sample_board(vec![
+            member(1, "Alice", 1669871400, 3),
+            member(2, "Bob", 1669872000, 2),
+            member(3, "Carol", 1669874400, 4),
+        ]);
+        let teams = HashMap::from([
+            ("Backend".to_owned(), vec![1, 2]),
+            ("Ops".to_owned(), vec![3, 4]),
+        ]);
+
+        let scores = team_scores(&leaderboard, 2022, &teams);
+        assert_eq!(scores[0].name, "Backend");
+        assert_eq!(scores[0].local_score, 5);
+        assert_eq!(scores[0].stars, 2);
+        // 10 and 20 minutes
+        assert_eq!(scores[0].average_time(), Some(Duration::minutes(15)));
+        // Member 4 isn't on the leaderboard
+        assert_eq!(scores[1].members, 1);
+    }
+}
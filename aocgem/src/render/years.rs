@@ -0,0 +1,229 @@
+use std::{collections::HashMap, error::Error, time::SystemTime};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    fetch::{get_leaderboard, FetchError},
+    leaderboard::{est_from_timestamp, latest_year, Leaderboard},
+};
+
+use super::{render_aoc_link, render_difference, render_duration};
+
+/// A member's results over every year they were part of the leaderboard
+struct History {
+    /// The name from the latest year
+    name: String,
+    stars: i32,
+    /// The average time for each star in the years the member got any
+    averages: Vec<(i32, Duration)>,
+}
+
+/// Combine the members of every year, `years` has to be in order
+fn histories(years: &[(i32, Leaderboard)]) -> Vec<History> {
+    let mut histories: HashMap<i32, History> = HashMap::new();
+    for (year, leaderboard) in years {
+        for member in leaderboard.members.values() {
+            let history = histories.entry(member.id).or_insert_with(|| History {
+                name: String::new(),
+                stars: 0,
+                averages: Vec::new(),
+            });
+            history.name = member.name.clone();
+            history.stars += member.stars;
+            if member.stars > 0 {
+                if let Some(total) = member.total_completion_time(*year) {
+                    history.averages.push((*year, total / member.stars));
+                }
+            }
+        }
+    }
+
+    let mut histories: Vec<_> = histories.into_values().collect();
+    histories.sort_unstable_by(|a, b| b.stars.cmp(&a.stars).then_with(|| a.name.cmp(&b.name)));
+    histories
+}
+
+/// How much the average changed since the year before the latest one the member took part in
+fn render_change(history: &History) -> String {
+    match history.averages.as_slice() {
+        [.., (_, previous), (_, latest)] => render_difference(&(*latest - *previous)),
+        _ => "--".to_owned(),
+    }
+}
+
+fn render_summary(histories: &[History]) -> String {
+    let n_t = "";
+    let stars_t = "Stars";
+    let years_t = "Years";
+    let latest_t = "Latest";
+    let change_t = "Change";
+    let name_t = "Name";
+
+    let n_w = histories.len().to_string().len() + 1;
+    let stars_w = histories
+        .iter()
+        .map(|history| history.stars.to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max(stars_t.len());
+    let change_w = histories
+        .iter()
+        .map(|history| render_change(history).len())
+        .max()
+        .unwrap_or(0)
+        .max(change_t.len());
+
+    let mut buffer = format!(
+        "{n_t:<n_w$} {stars_t:<stars_w$} {years_t} {latest_t:<8} {change_t:<change_w$} {name_t}"
+    );
+    for (i, history) in histories.iter().enumerate() {
+        let i = format!("{}.", i + 1);
+        let stars = history.stars;
+        let years = history.averages.len();
+        let latest = match history.averages.last() {
+            Some((_, average)) => render_duration(average),
+            None => "--:--:--".to_owned(),
+        };
+        let change = render_change(history);
+        let name = &history.name;
+        buffer += &format!(
+            "\n{i:>n_w$} {stars:>stars_w$} {years:>5} {latest:>8} {change:>change_w$} {name}"
+        );
+    }
+    buffer
+}
+
+/// The average time for each star of every member, one column for each year
+fn render_averages(histories: &[History], years: &[i32]) -> String {
+    let mut buffer = String::new();
+    for year in years {
+        buffer += &format!("{year:<8} ");
+    }
+    buffer += "Name";
+
+    for history in histories {
+        buffer += "\n";
+        for year in years {
+            let average = match history.averages.iter().find(|(other, _)| other == year) {
+                Some((_, average)) => render_duration(average),
+                None => "--:--:--".to_owned(),
+            };
+            buffer += &format!("{average:>8} ");
+        }
+        buffer += &history.name;
+    }
+    buffer
+}
+
+/// Every year of a leaderboard that could be loaded, in order
+pub struct Years {
+    pub loaded: Vec<(i32, Leaderboard)>,
+    /// The years that couldn't be loaded, and why
+    pub skipped: Vec<(i32, FetchError)>,
+}
+
+/// Load every year of the leaderboard since 2015
+///
+/// Years that can't be loaded are skipped along with the reason, only errors
+/// from the cache itself are returned.
+pub fn load_years(session: &str, id: &str) -> Result<Years, Box<dyn Error>> {
+    let now = est_from_timestamp(DateTime::<Utc>::from(SystemTime::now()).timestamp());
+
+    let mut years = Vec::new();
+    let mut skipped = Vec::new();
+    // The latest years first, they are the most likely to exist when the rate limit runs out
+    for year in (2015..=latest_year(now)).rev() {
+        match get_leaderboard(session, year, id) {
+            Ok(fetched) => years.push((year, fetched.leaderboard)),
+            Err(FetchError::Storage(err)) => return Err(err),
+            Err(err) => skipped.push((year, err)),
+        }
+    }
+    years.reverse();
+    skipped.reverse();
+    Ok(Years {
+        loaded: years,
+        skipped,
+    })
+}
+
+/// Render every year of the leaderboard since 2015
+///
+/// Years that can't be loaded are skipped and listed with the reason.
+pub fn render_years(session: &str, id: &str) -> Result<String, Box<dyn Error>> {
+    let Years {
+        loaded: years,
+        skipped,
+    } = load_years(session, id)?;
+
+    if years.is_empty() {
+        return Ok("\nThe leaderboard couldn't be loaded for any year.\n".to_owned());
+    }
+
+    let aoc_link = render_aoc_link(years[years.len() - 1].0, id);
+    let histories = histories(&years);
+    let years: Vec<_> = years.into_iter().map(|(year, _)| year).collect();
+    let summary = render_summary(&histories);
+    let averages = render_averages(&histories, &years);
+
+    let skipped = match skipped.is_empty() {
+        true => String::new(),
+        false => format!(
+            "
+## Skipped years
+
+These years couldn't be loaded for the leaderboard.
+
+{}
+",
+            skipped
+                .iter()
+                .map(|(year, err)| format!("* {year}: {err}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    };
+
+    Ok(format!(
+        "
+{aoc_link}
+
+The stars of every member added up over all years. Years counts the years the member got a star, and Latest is their average time for each star in the latest of them. Change is how much that average moved since the previous year they took part in.
+
+```all years
+{summary}
+```
+
+## Average time for each star
+
+```yearly averages
+{averages}
+```
+{skipped}"
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::leaderboard::test::{sample_board, sample_member};
+
+    /// Alice with the first star of day 1 at `day_1`
+    fn leaderboard(day_1: i64) -> Leaderboard {
+        sample_board(vec![sample_member(1, "Alice", &[(1, 1, day_1, 1)])])
+    }
+
+    #[test]
+    fn test_histories() {
+        // An hour for the first star of 2021, half an hour in 2022
+        let years = vec![
+            (2021, leaderboard(1638334800 + 3600)),
+            (2022, leaderboard(1669870800 + 1800)),
+        ];
+        let histories = histories(&years);
+
+        assert_eq!(histories[0].stars, 2);
+        assert_eq!(histories[0].averages.len(), 2);
+        assert_eq!(render_change(&histories[0]), "-00:30:00");
+    }
+}
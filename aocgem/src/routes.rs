@@ -1,14 +1,95 @@
 pub mod day;
+pub mod history;
 pub mod main;
-
-use crate::cgi::{get_script, Response, Result};
+pub mod member;
+pub mod ranks;
+pub mod teams;
+pub mod years;
+
+use crate::{
+    cgi::{ClientCert, Error, Request, Response, Result},
+    fetch::{fetch_member_id, load_identity, save_identity},
+    leaderboard::{board_ids, Identity, PublicLeaderboard},
+};
+use regex::Regex;
 use route_recognizer::{Params, Router};
 
-pub type FnRoute = dyn Fn(&Params) -> Result<Response>;
+pub type FnRoute = dyn Fn(&Request, &Params) -> Result<Response>;
+
+/// Get the session from the user's input
+pub fn parse_session(query: &str) -> &str {
+    // Account for copying from firefox (session:"{session}")
+    let regex = Regex::new(r#""(.*)""#).unwrap();
+    if let Some(captures) = regex.captures(query) {
+        captures.get(1).unwrap().as_str()
+    } else {
+        query
+    }
+}
+
+/// Get the client certificate, asking for one if there isn't any
+pub fn require_cert(request: &Request) -> Result<&ClientCert> {
+    request.cert.as_ref().ok_or_else(|| {
+        Error::response(Response::cert_required(
+            "A client certificate is required to log in",
+        ))
+    })
+}
+
+/// Get the session linked to the client certificate
+///
+/// If the certificate has no session yet, the user is sent to the login page.
+pub fn require_identity(request: &Request) -> Result<Identity> {
+    let cert = require_cert(request)?;
+    match load_identity(&cert.hash)? {
+        Some(identity) => Ok(identity),
+        None => {
+            let script = request.script();
+            Err(Error::response_with_message(
+                "No session is linked to the certificate",
+                Response::redirect(format!("{script}/me/login/")),
+            ))
+        }
+    }
+}
+
+/// Whether the member owns a public leaderboard, or any part of a meta board
+///
+/// adventofcode.com gives a private leaderboard the id of the member who owns
+/// it, the same as `Leaderboard.owner_id`. Whoever's session the leaderboard
+/// is fetched with doesn't matter, any member can renew it.
+pub fn is_owner(member: i32, pub_board: &PublicLeaderboard) -> bool {
+    let member = member.to_string();
+    board_ids(&pub_board.id).contains(&member.as_str())
+}
+
+/// Make sure the client certificate belongs to the owner of a public leaderboard
+pub fn require_owner(request: &Request, pub_board: &PublicLeaderboard) -> Result<Identity> {
+    let mut identity = require_identity(request)?;
+    if !is_owner(member_id(&mut identity)?, pub_board) {
+        return Err(Error::response(Response::cert_not_authorized(
+            "Only the owner of the leaderboard can change it",
+        )));
+    }
+    Ok(identity)
+}
+
+/// Get the leaderboard member the identity's session belongs to
+///
+/// The id is looked up on adventofcode.com the first time, and saved with the identity.
+pub fn member_id(identity: &mut Identity) -> Result<i32> {
+    if let Some(member) = identity.member {
+        return Ok(member);
+    }
+    let member = fetch_member_id(&identity.session)?;
+    identity.member = Some(member);
+    save_identity(identity)?;
+    Ok(member)
+}
 
-fn root(_params: &Params) -> Result<Response> {
+fn root(request: &Request, _params: &Params) -> Result<Response> {
     // /
-    let script = get_script();
+    let script = request.script();
 
     Ok(Response::success(
         "text/gemini",
@@ -30,6 +111,13 @@ Public leaderboards are not advertised anywhere, so you will need to have a frie
 
 You can view a leaderboard without making it public. You will just need your session key and a leaderboard id. If you want to make the leaderboard public, there will be an option to publish it at the bottom of the main leaderboard.
 
+### Log in with a client certificate
+
+If your client supports client certificates, you can link your session key to a certificate. Your session key is stored on the server and will never show up in a url, so it is safe to share any link you visit.
+
+=> {script}/me/ View your leaderboard with a client certificate
+=> {script}/me/logout/ Unlink your session key from your certificate
+
 ### How to get your session key
 
 When you log into adventofcode.com, it will save a cookie called 'session'. To find it, press F12 while on the site, navigate to the 'storage' or 'application' tab, then 'cookies', 'adventofcode.com' and you should see the session cookie. Copy the value for later.
@@ -47,9 +135,11 @@ https://adventofcode.com/2022/leaderboard/private/view/123456
 
 => https://adventofcode.com/2022/leaderboard/private View your leaderboards
 
+To view several leaderboards as one, enter all of their ids separated by commas, like `123456, 234567`. Members of more than one of them are only counted once, and the local scores are worked out again for everyone. Your session has to be a member of every leaderboard.
+
 ### Once you have your session key and leaderboard id
 
-Follow the link below where you will be asked for your session key and leaderboard id. Once you enter them in, you will be able to view the leaderboard. If you want to make it public, you may do so from there
+Follow the link below where you will be asked for your session key and leaderboard id. Once you enter them in, you will be able to view the leaderboard. If you want to make it public, you may do so from there. Note that your session key will be part of the url, so don't share these links with anyone.
 
 => {script}/session/ Login to view your leaderboard
 
@@ -62,4 +152,26 @@ pub fn add_routes(router: &mut Router<&FnRoute>) {
     router.add("/", &root);
     main::add_routes(router);
     day::add_routes(router);
+    history::add_routes(router);
+    ranks::add_routes(router);
+    member::add_routes(router);
+    years::add_routes(router);
+    teams::add_routes(router);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_owner() {
+        let pub_board = PublicLeaderboard::new("tok", "123", "session");
+        assert!(is_owner(123, &pub_board));
+        assert!(!is_owner(12, &pub_board));
+
+        // The owner of any part owns a meta board
+        let pub_board = PublicLeaderboard::new("tok", "123+456", "session");
+        assert!(is_owner(456, &pub_board));
+        assert!(!is_owner(789, &pub_board));
+    }
 }
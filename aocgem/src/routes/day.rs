@@ -1,22 +1,31 @@
 use crate::{
-    cgi::{get_query, get_script, OkResponse, Response, Result},
+    cgi::{OkResponse, Request, Response, Result},
     fetch::load_pub_leaderboard,
-    render::day::render_day,
+    leaderboard::Settings,
+    render::{
+        day::{export_day, render_day},
+        export::Format,
+    },
 };
 use route_recognizer::{Params, Router};
 
-use super::FnRoute;
+use super::{require_identity, FnRoute};
 
-fn view_session_day(params: &Params) -> Result<Response> {
+fn view_session_day(request: &Request, params: &Params) -> Result<Response> {
     let session = params.find("session").unwrap();
     let board_id = params.find("leaderboard").unwrap();
     let year = params.find("year").unwrap();
-    let year = i32::from_str_radix(year, 10).ok_or_response(Response::not_found())?;
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
     let day = params.find("day").unwrap();
-    let day = u32::from_str_radix(day, 10).ok_or_response(Response::not_found())?;
+    let day = day.parse::<u32>().ok_or_response(Response::not_found())?;
 
-    let leaderboard = render_day(session, year, day, board_id)?;
-    let script = get_script();
+    if let Some(format) = Format::from_request(request)? {
+        let settings = Settings::default();
+        return export_day(request, session, year, day, board_id, &settings, format);
+    }
+
+    let leaderboard = render_day(request, session, year, day, board_id, &Settings::default())?;
+    let script = request.script();
 
     let links = match day {
         1 => format!("=> {script}/session/{session}/{board_id}/{year}/2/ View day 2"),
@@ -51,17 +60,100 @@ Here are the times for Day {day}.
     ))
 }
 
-fn view_public_day(params: &Params) -> Result<Response> {
+fn view_my_day(request: &Request, params: &Params) -> Result<Response> {
+    let identity = require_identity(request)?;
     let board_id = params.find("leaderboard").unwrap();
     let year = params.find("year").unwrap();
-    let year = i32::from_str_radix(year, 10).ok_or_response(Response::not_found())?;
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
     let day = params.find("day").unwrap();
-    let day = u32::from_str_radix(day, 10).ok_or_response(Response::not_found())?;
+    let day = day.parse::<u32>().ok_or_response(Response::not_found())?;
+
+    if let Some(format) = Format::from_request(request)? {
+        let settings = Settings::default();
+        return export_day(
+            request,
+            &identity.session,
+            year,
+            day,
+            board_id,
+            &settings,
+            format,
+        );
+    }
+
+    let leaderboard = render_day(
+        request,
+        &identity.session,
+        year,
+        day,
+        board_id,
+        &Settings::default(),
+    )?;
+    let script = request.script();
+
+    let links = match day {
+        1 => format!("=> {script}/me/{board_id}/{year}/2/ View day 2"),
+        25 => format!("=> {script}/me/{board_id}/{year}/24/ View day 24"),
+        _ => {
+            let yesterday = day - 1;
+            let tomorrow = day + 1;
+            format!(
+                "=> {script}/me/{board_id}/{year}/{yesterday}/ View day {yesterday}
+=> {script}/me/{board_id}/{year}/{tomorrow}/ View day {tomorrow}"
+            )
+        }
+    };
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# Advent of Code Leaderboard {year}, Day {day}
+
+=> {script}/me/{board_id}/{year}/ Back to the main leaderboard
+
+Here are the times for Day {day}.
+
+{leaderboard}
+
+## View another day
+
+{links}
+"
+        ),
+    ))
+}
+
+fn view_public_day(request: &Request, params: &Params) -> Result<Response> {
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+    let day = params.find("day").unwrap();
+    let day = day.parse::<u32>().ok_or_response(Response::not_found())?;
 
     let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
 
-    let leaderboard = render_day(&pub_board.session, year, day, &pub_board.id)?;
-    let script = get_script();
+    if let Some(format) = Format::from_request(request)? {
+        return export_day(
+            request,
+            &pub_board.session,
+            year,
+            day,
+            &pub_board.id,
+            &pub_board.settings,
+            format,
+        );
+    }
+
+    let leaderboard = render_day(
+        request,
+        &pub_board.session,
+        year,
+        day,
+        &pub_board.id,
+        &pub_board.settings,
+    )?;
+    let script = request.script();
 
     let links = match day {
         1 => format!("=> {script}/leaderboard/{board_id}/{year}/2/ View day 2"),
@@ -96,14 +188,14 @@ Here are the times for Day {day}.
     ))
 }
 
-fn select_day(params: &Params) -> Result<Response> {
+fn select_day(request: &Request, params: &Params) -> Result<Response> {
     let session = params.find("session").unwrap();
     let board_id = params.find("leaderboard").unwrap();
     let year = params.find("year").unwrap();
-    let year = i32::from_str_radix(year, 10).ok_or_response(Response::not_found())?;
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
 
-    let script = get_script();
-    let query = get_query();
+    let script = request.script();
+    let query = request.query();
     if query.is_empty() {
         Ok(Response::input("Which day would you like to view?"))
     } else {
@@ -113,13 +205,30 @@ fn select_day(params: &Params) -> Result<Response> {
     }
 }
 
-fn select_pub_day(params: &Params) -> Result<Response> {
+fn select_my_day(request: &Request, params: &Params) -> Result<Response> {
+    require_identity(request)?;
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let script = request.script();
+    let query = request.query();
+    if query.is_empty() {
+        Ok(Response::input("Which day would you like to view?"))
+    } else {
+        Ok(Response::redirect(format!(
+            "{script}/me/{board_id}/{year}/{query}/"
+        )))
+    }
+}
+
+fn select_pub_day(request: &Request, params: &Params) -> Result<Response> {
     let board_id = params.find("leaderboard").unwrap();
     let year = params.find("year").unwrap();
-    let year = i32::from_str_radix(year, 10).ok_or_response(Response::not_found())?;
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
 
-    let script = get_script();
-    let query = get_query();
+    let script = request.script();
+    let query = request.query();
     if query.is_empty() {
         Ok(Response::input("Which day would you like to view?"))
     } else {
@@ -135,6 +244,8 @@ pub fn add_routes(router: &mut Router<&FnRoute>) {
         &view_session_day,
     );
     router.add("/session/:session/:leaderboard/:year/day", &select_day);
+    router.add("/me/:leaderboard/:year/day", &select_my_day);
+    router.add("/me/:leaderboard/:year/:day", &view_my_day);
     router.add("/leaderboard/:leaderboard/:year/day", &select_pub_day);
     router.add("/leaderboard/:leaderboard/:year/:day", &view_public_day);
 }
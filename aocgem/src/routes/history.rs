@@ -0,0 +1,264 @@
+use chrono::{NaiveDate, NaiveDateTime, TimeZone};
+use route_recognizer::{Params, Router};
+use urlencoding::decode;
+
+use crate::{
+    cgi::{Error, OkResponse, Request, Response, Result},
+    fetch::{load_pub_leaderboard, load_snapshot, Snapshot},
+    leaderboard::{est_offset, PublicLeaderboard},
+    render::{
+        day::{export_day_snapshot, render_day_snapshot},
+        export::Format,
+        main::{export_snapshot, render_snapshot},
+        member::render_member,
+        render_taken,
+    },
+};
+
+use super::FnRoute;
+
+/// Parse a unix timestamp, or a date and time in EST
+fn parse_time(input: &str) -> Option<i64> {
+    let input = input.trim();
+    if let Ok(timestamp) = input.parse::<i64>() {
+        return Some(timestamp);
+    }
+    let time = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    est_offset()
+        .from_local_datetime(&time)
+        .single()
+        .map(|time| time.timestamp())
+}
+
+/// Load the public leaderboard and the snapshot the route points to
+fn find_snapshot(params: &Params) -> Result<(PublicLeaderboard, i32, Snapshot)> {
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+    let at = params.find("timestamp").unwrap();
+    let at = at.parse::<i64>().ok_or_response(Response::not_found())?;
+
+    let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+    let snapshot = load_snapshot(&pub_board.id, year, at)?.ok_or_else(|| {
+        Error::response(Response::perm_error(
+            "There are no snapshots from before then",
+        ))
+    })?;
+    Ok((pub_board, year, snapshot))
+}
+
+fn select_time(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/at/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let script = request.script();
+    let query = request.query();
+    let query = decode(query).ok_or_response(Response::bad_request())?;
+    if query.is_empty() {
+        return Ok(Response::input(
+            "When would you like to view the leaderboard? (YYYY-MM-DD HH:MM EST)",
+        ));
+    }
+    match parse_time(&query) {
+        Some(at) => Ok(Response::redirect(format!(
+            "{script}/leaderboard/{board_id}/{year}/at/{at}/"
+        ))),
+        None => Ok(Response::input("Invalid time, use YYYY-MM-DD HH:MM in EST")),
+    }
+}
+
+fn view_snapshot(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/at/:timestamp/
+    let (pub_board, year, snapshot) = find_snapshot(params)?;
+    let board_id = &pub_board.token;
+
+    if let Some(format) = Format::from_request(request)? {
+        return Ok(export_snapshot(
+            request,
+            &snapshot,
+            &pub_board.settings,
+            format,
+        )?);
+    }
+
+    let leaderboard =
+        render_snapshot(request, &snapshot, year, &pub_board.id, &pub_board.settings)?;
+    let script = request.script();
+
+    let mut links = Vec::new();
+    if let Some(previous) = snapshot.previous {
+        links.push(format!(
+            "=> {script}/leaderboard/{board_id}/{year}/at/{previous}/ Previous snapshot"
+        ));
+    }
+    if let Some(next) = snapshot.next {
+        links.push(format!(
+            "=> {script}/leaderboard/{board_id}/{year}/at/{next}/ Next snapshot"
+        ));
+    }
+    let links = links.join("\n");
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# Advent of Code Leaderboard {year}
+
+=> {script}/leaderboard/{board_id}/{year}/ Back to the current leaderboard
+
+The overall scores for each person in the leaderboard.
+
+{leaderboard}
+
+## View a different time
+
+{links}
+=> {script}/leaderboard/{board_id}/{year}/at/ Enter a different time
+"
+        ),
+    ))
+}
+
+fn view_snapshot_day(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/at/:timestamp/:day/
+    let (pub_board, year, snapshot) = find_snapshot(params)?;
+    let board_id = &pub_board.token;
+    let at = params.find("timestamp").unwrap();
+    let day = params.find("day").unwrap();
+    let day = day.parse::<u32>().ok_or_response(Response::not_found())?;
+
+    if let Some(format) = Format::from_request(request)? {
+        return export_day_snapshot(request, &snapshot, year, day, &pub_board.settings, format);
+    }
+
+    let leaderboard = render_day_snapshot(
+        request,
+        &snapshot,
+        year,
+        day,
+        &pub_board.id,
+        &pub_board.settings,
+    )?;
+    let script = request.script();
+
+    let links = match day {
+        1 => format!("=> {script}/leaderboard/{board_id}/{year}/at/{at}/2/ View day 2"),
+        25 => format!("=> {script}/leaderboard/{board_id}/{year}/at/{at}/24/ View day 24"),
+        _ => {
+            let yesterday = day - 1;
+            let tomorrow = day + 1;
+            format!(
+                "=> {script}/leaderboard/{board_id}/{year}/at/{at}/{yesterday}/ View day {yesterday}
+=> {script}/leaderboard/{board_id}/{year}/at/{at}/{tomorrow}/ View day {tomorrow}"
+            )
+        }
+    };
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# Advent of Code Leaderboard {year}, Day {day}
+
+=> {script}/leaderboard/{board_id}/{year}/at/{at}/ Back to the leaderboard
+
+Here are the times for Day {day}.
+
+{leaderboard}
+
+## View another day
+
+{links}
+"
+        ),
+    ))
+}
+
+fn view_snapshot_member(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/at/:timestamp/member/:member/
+    let (pub_board, year, snapshot) = find_snapshot(params)?;
+    let board_id = &pub_board.token;
+    let at = params.find("timestamp").unwrap();
+    let member = params.find("member").unwrap();
+
+    let leaderboard = &snapshot.leaderboard;
+    let profile = match render_member(leaderboard, year, member) {
+        Some(profile) => profile,
+        None => return Ok(Response::not_found()),
+    };
+    let name = &leaderboard.members[member].name;
+    let taken = render_taken(snapshot.taken);
+    let script = request.script();
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# {name}, Advent of Code {year}
+
+=> {script}/leaderboard/{board_id}/{year}/at/{at}/ Back to the leaderboard
+=> {script}/leaderboard/{board_id}/{year}/member/{member}/ View the current times
+{taken}{profile}"
+        ),
+    ))
+}
+
+fn select_snapshot_day(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/at/:timestamp/day/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+    let at = params.find("timestamp").unwrap();
+
+    let script = request.script();
+    let query = request.query();
+    if query.is_empty() {
+        Ok(Response::input("Which day would you like to view?"))
+    } else {
+        Ok(Response::redirect(format!(
+            "{script}/leaderboard/{board_id}/{year}/at/{at}/{query}/"
+        )))
+    }
+}
+
+pub fn add_routes(router: &mut Router<&FnRoute>) {
+    router.add("/leaderboard/:leaderboard/:year/at", &select_time);
+    router.add(
+        "/leaderboard/:leaderboard/:year/at/:timestamp",
+        &view_snapshot,
+    );
+    router.add(
+        "/leaderboard/:leaderboard/:year/at/:timestamp/day",
+        &select_snapshot_day,
+    );
+    router.add(
+        "/leaderboard/:leaderboard/:year/at/:timestamp/:day",
+        &view_snapshot_day,
+    );
+    router.add(
+        "/leaderboard/:leaderboard/:year/at/:timestamp/member/:member",
+        &view_snapshot_member,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1670821200"), Some(1670821200));
+        // Midnight EST on Dec 12 2022
+        assert_eq!(parse_time("2022-12-12"), Some(1670821200));
+        assert_eq!(parse_time("2022-12-12 01:30"), Some(1670826600));
+        assert_eq!(parse_time("yesterday"), None);
+    }
+}
@@ -1,49 +1,85 @@
 use crate::{
-    cgi::{get_query, get_script, OkResponse, Response, Result},
+    cgi::{Error, OkResponse, Request, Response, Result},
     fetch::{
-        fetch_leaderboard, find_pub_leaderboard, load_pub_leaderboard, pub_leaderboard_exists,
-        save_pub_leaderboard,
+        self, delete_identity, fetch_member_id, find_pub_leaderboard, load_pub_leaderboard,
+        lock_pub_leaderboard, new_pub_token, pub_leaderboard_exists, renew_leaderboard,
+        save_identity, save_pub_leaderboard, FetchError,
     },
-    leaderboard::PublicLeaderboard,
-    render::main::render_leaderboard,
+    leaderboard::{parse_board_ids, parse_start, Identity, PublicLeaderboard, Settings},
+    render::{
+        export::Format,
+        main::{export_leaderboard, render_leaderboard},
+    },
+    scoring::Scheme,
 };
-use rand::distributions::{Alphanumeric, DistString};
-use regex::Regex;
 use route_recognizer::{Params, Router};
 use urlencoding::decode;
 
-use super::FnRoute;
+use super::{
+    is_owner, member_id, parse_session, require_cert, require_identity, require_owner, FnRoute,
+};
+
+const INVALID_BOARD_IDS: &str =
+    "Invalid leaderboard id, enter the number at the end of the url, or several separated by commas";
 
-fn renew_pub_leaderboard(params: &Params) -> Result<Response> {
+/// Turn the reasons a session can't see a leaderboard into a message for the user
+fn session_error(err: FetchError, board_id: &str, year: i32) -> Error {
+    let response = match err {
+        FetchError::Unauthorized => Response::perm_error("You must provide a valid session"),
+        FetchError::Forbidden => {
+            Response::perm_error("That session is not a member of this leaderboard")
+        }
+        FetchError::NotFound => {
+            Response::perm_error(format!("Leaderboard {board_id} does not exist for {year}"))
+        }
+        err => return err.into(),
+    };
+    Error::response(response)
+}
+
+/// Make sure the session can see the leaderboard before it is published
+///
+/// The leaderboard is loaded through the cache, so the views that led here
+/// don't count against the rate limit a second time, and the page the user
+/// is sent to afterwards is already cached. A cached leaderboard that can't be
+/// refreshed with the session is not good enough.
+fn check_session(session: &str, board_id: &str, year: i32) -> Result<()> {
+    match fetch::get_leaderboard(session, year, board_id) {
+        Ok(fetched) => match fetched.stale {
+            Some(stale) => Err(Error::response(Response::perm_error(format!(
+                "The leaderboard couldn't be loaded with that session: {}",
+                stale.reason
+            )))),
+            None => Ok(()),
+        },
+        Err(err) => Err(session_error(err, board_id, year)),
+    }
+}
+
+fn renew_pub_leaderboard(request: &Request, params: &Params) -> Result<Response> {
     // /leaderboard/:leaderboard/:year/renew/
     let board_id = params.find("leaderboard").unwrap();
     let year = params.find("year").unwrap();
-    let year = i32::from_str_radix(year, 10).ok_or_response(Response::not_found())?;
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
 
-    let script = get_script();
-    let query = get_query();
-    let query = decode(&query).ok_or_response(Response::bad_request())?;
+    let script = request.script();
+    let query = request.query();
+    let query = decode(query).ok_or_response(Response::bad_request())?;
     if query.is_empty() {
         Ok(Response::input("Enter your session key"))
     } else {
-        // Account for copying from firefox (session:"{session}")
-        let regex = Regex::new(r#""(.*)""#).unwrap();
-        let session = if let Some(captures) = regex.captures(&query) {
-            captures.get(1).unwrap().as_str()
-        } else {
-            &query
-        };
+        let session = parse_session(&query);
 
+        let _lock = lock_pub_leaderboard(board_id)?;
         let mut pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
 
-        // Make sure the session is valid
-        if let Err(_) = fetch_leaderboard(&session, &pub_board.id, year) {
-            return Ok(Response::perm_error("You must provide a valid session"));
-        }
+        // The current session stopped working, so the new one has to be checked with adventofcode.com
+        renew_leaderboard(session, &pub_board.id, year)
+            .map_err(|err| session_error(err, &pub_board.id, year))?;
 
         pub_board.session = session.to_owned();
 
-        save_pub_leaderboard(&board_id, &pub_board).unwrap();
+        save_pub_leaderboard(board_id, &pub_board).unwrap();
 
         Ok(Response::redirect(format!(
             "{script}/leaderboard/{board_id}/{year}/"
@@ -51,16 +87,168 @@ fn renew_pub_leaderboard(params: &Params) -> Result<Response> {
     }
 }
 
-fn view_pub_leaderboard(params: &Params) -> Result<Response> {
+fn select_pub_scheme(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/scoring/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let _lock = lock_pub_leaderboard(board_id)?;
+    let mut pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+    require_owner(request, &pub_board)?;
+
+    let script = request.script();
+    let query = request.query();
+    let keys = Scheme::ALL.map(|scheme| scheme.key()).join(", ");
+    if query.is_empty() {
+        return Ok(Response::input(format!(
+            "How should members be ranked? ({keys})"
+        )));
+    }
+    match Scheme::from_key(&query.to_lowercase()) {
+        Some(scheme) => {
+            pub_board.settings.scheme = scheme;
+            save_pub_leaderboard(board_id, &pub_board)?;
+            Ok(Response::redirect(format!(
+                "{script}/leaderboard/{board_id}/{year}/"
+            )))
+        }
+        None => Ok(Response::input(format!(
+            "Unknown scheme, choose one of {keys}"
+        ))),
+    }
+}
+
+fn list_pub_starts(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/start/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+    let leaderboard = fetch::get_leaderboard(&pub_board.session, year, &pub_board.id)?.leaderboard;
+
+    let script = request.script();
+    let mut members: Vec<_> = leaderboard.members.values().collect();
+    members.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    let links = members
+        .into_iter()
+        .map(|member| {
+            let id = member.id;
+            let name = &member.name;
+            let start = match pub_board.settings.starts.get(&id.to_string()) {
+                Some(start) => format!("{:02}:{:02} EST", start / 3600, start / 60 % 60),
+                None => "midnight EST".to_owned(),
+            };
+            format!(
+                "=> {script}/leaderboard/{board_id}/{year}/start/{id}/ {name} (starts at {start})"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# When does everyone start?
+
+=> {script}/leaderboard/{board_id}/{year}/ Back to the leaderboard
+
+Puzzles unlock at midnight EST, which isn't a reasonable time for everyone. Set when you usually start, and the leaderboard will also show your times counted from then.
+
+Setting your start needs a client certificate linked to your session key, so that nobody else can change it. The owner of the leaderboard can set everyone's start.
+
+{links}
+"
+        ),
+    ))
+}
+
+fn set_pub_start(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/start/:member/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+    let member = params.find("member").unwrap();
+    let member = member
+        .parse::<i32>()
+        .ok_or_response(Response::not_found())?;
+
+    let _lock = lock_pub_leaderboard(board_id)?;
+    let mut pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+
+    // Members set their own start, and the owner can set anyone's
+    let mut identity = require_identity(request)?;
+    let id = member_id(&mut identity)?;
+    if id != member && !is_owner(id, &pub_board) {
+        return Ok(Response::cert_not_authorized(
+            "You can only set your own start time",
+        ));
+    }
+    let member = member.to_string();
+
+    let script = request.script();
+    let query = request.query();
+    let query = decode(query).ok_or_response(Response::bad_request())?;
+    if query.is_empty() {
+        return Ok(Response::input(
+            "When do you usually start? (HH:MM with a UTC offset like 07:00 +01:00, or `none`)",
+        ));
+    }
+    if query.eq_ignore_ascii_case("none") {
+        pub_board.settings.starts.remove(&member);
+    } else {
+        match parse_start(&query) {
+            Some(start) => {
+                pub_board.settings.starts.insert(member, start);
+            }
+            None => {
+                return Ok(Response::input(
+                    "Invalid time, use HH:MM with a UTC offset like 07:00 +01:00",
+                ))
+            }
+        }
+    }
+    save_pub_leaderboard(board_id, &pub_board)?;
+    Ok(Response::redirect(format!(
+        "{script}/leaderboard/{board_id}/{year}/start/"
+    )))
+}
+
+fn view_pub_leaderboard(request: &Request, params: &Params) -> Result<Response> {
     // /leaderboard/:leaderboard/:year/
     let board_id = params.find("leaderboard").unwrap();
     let year = params.find("year").unwrap();
-    let year = i32::from_str_radix(year, 10).ok_or_response(Response::not_found())?;
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
 
     let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
 
-    let script = get_script();
-    match render_leaderboard(&pub_board.session, year, &pub_board.id) {
+    if let Some(format) = Format::from_request(request)? {
+        return Ok(export_leaderboard(
+            request,
+            &pub_board.session,
+            year,
+            &pub_board.id,
+            &pub_board.settings,
+            format,
+        )?);
+    }
+
+    let script = request.script();
+    let scheme = pub_board.settings.scheme.name();
+    let imported = if pub_board.is_view_only() {
+        "\nThis leaderboard was imported from a file, so it only updates when it is imported again.\n"
+    } else {
+        ""
+    };
+    match render_leaderboard(
+        request,
+        &pub_board.session,
+        year,
+        &pub_board.id,
+        &pub_board.settings,
+    ) {
         Ok(leaderboard) => Ok(Response::success(
             "text/gemini",
             format!(
@@ -68,25 +256,26 @@ fn view_pub_leaderboard(params: &Params) -> Result<Response> {
 # Advent of Code Leaderboard {year}
 
 => {script}/leaderboard/{board_id}/ View a different year
+=> {script}/leaderboard/{board_id}/all/ View all years
+=> {script}/leaderboard/{board_id}/{year}/ranks/ View the ranks over time
+=> {script}/leaderboard/{board_id}/{year}/teams/ View the teams
 
 The overall scores for each person in the leaderboard.
 
 {leaderboard}
+{imported}
+=> {script}/leaderboard/{board_id}/{year}/at/ View the leaderboard at an earlier time
+=> {script}/leaderboard/{board_id}/{year}/scoring/ Change how members are ranked ({scheme}, owner only)
+=> {script}/leaderboard/{board_id}/{year}/start/ Set when you usually start
 
 This is a shared leaderboard, anyone with the link can view it. Share this token with your friends: `{board_id}`. 
 "
             ),
         )),
-        Err(_) => {
-            // The session is invalid
-            Ok(Response::success("text/gemini", format!("
+        Err(err) => match err.downcast_ref() {
+            Some(FetchError::Unauthorized) => Ok(Response::success("text/gemini", format!("
 # Session has expired 
 
-If you think you entered an invalid year, 
-
-=> {script}/leaderboard/{board_id}/ Enter a different year 
-
-
 The session for this leaderboard has expired. To renew the leaderboard, a new session key will need to be provided. If you have access to the leaderboard, you can renew session with your own session key.
 
 => {script}/leaderboard/{board_id}/{year}/renew/ Renew the leaderboard
@@ -97,12 +286,27 @@ When you log into adventofcode.com, it will save a cookie called 'session'. To f
 
 => https://adventofcode.com adventofcode.com
 
-")))
-        }
+"))),
+            Some(FetchError::Forbidden) => Ok(Response::success("text/gemini", format!("
+# No access to this leaderboard
+
+The session for this leaderboard is no longer a member of it, the owner may have removed them. If you are a member of the leaderboard, you can renew it with your own session key.
+
+=> {script}/leaderboard/{board_id}/{year}/renew/ Renew the leaderboard
+"))),
+            Some(FetchError::NotFound) => Ok(Response::success("text/gemini", format!("
+# No leaderboard for {year}
+
+This leaderboard doesn't exist for {year}.
+
+=> {script}/leaderboard/{board_id}/ Enter a different year
+"))),
+            _ => Err(err.into()),
+        },
     }
 }
 
-fn get_pub_year(params: &Params) -> Result<Response> {
+fn get_pub_year(request: &Request, params: &Params) -> Result<Response> {
     // /leaderboard/:leaderboard/
     let board_token = params.find("leaderboard").unwrap();
 
@@ -110,8 +314,8 @@ fn get_pub_year(params: &Params) -> Result<Response> {
         return Ok(Response::not_found());
     }
 
-    let script = get_script();
-    let query = get_query();
+    let script = request.script();
+    let query = request.query();
     if query.is_empty() {
         Ok(Response::input("Which year would you like to view?"))
     } else {
@@ -121,11 +325,11 @@ fn get_pub_year(params: &Params) -> Result<Response> {
     }
 }
 
-fn get_pub_leaderboard(_params: &Params) -> Result<Response> {
+fn get_pub_leaderboard(request: &Request, _params: &Params) -> Result<Response> {
     // /leaderboard/
 
-    let script = get_script();
-    let query = get_query();
+    let script = request.script();
+    let query = request.query();
     if query.is_empty() {
         Ok(Response::input("Enter the leaderboard token"))
     } else {
@@ -133,65 +337,87 @@ fn get_pub_leaderboard(_params: &Params) -> Result<Response> {
     }
 }
 
-fn publish_leaderboard(params: &Params) -> Result<Response> {
-    // /session/:session/:leaderboard/:year/publish/
-    let session = params.find("session").unwrap();
-    let board_id = params.find("leaderboard").unwrap();
-    let year = params.find("year").unwrap();
-    let year = i32::from_str_radix(year, 10).ok_or_response(Response::not_found())?;
-
-    let query = get_query();
+/// Ask for confirmation, then publish the leaderboard using the session
+///
+/// If the user doesn't confirm, they are sent back to `back`.
+fn publish(
+    request: &Request,
+    session: &str,
+    board_id: &str,
+    year: i32,
+    back: &str,
+) -> Result<Response> {
+    let query = request.query();
     if query.is_empty() {
         Ok(Response::input(
             "Are you sure you want to publish this leaderboard? (enter `yes`)",
         ))
     } else {
-        let script = get_script();
+        let script = request.script();
         if query.to_lowercase() != "yes" {
-            Ok(Response::redirect(format!(
-                "{script}/session/{session}/{board_id}/{year}/"
-            )))
+            Ok(Response::redirect(back))
         } else {
-            // Make sure the session is valid
-            if let Err(_) = fetch_leaderboard(&session, &board_id, year) {
-                return Ok(Response::perm_error(
-                    "You must provide a valid session/leaderboard id",
-                ));
-            }
+            check_session(session, board_id, year)?;
 
-            if let Some(mut pub_board) = find_pub_leaderboard(board_id)? {
-                pub_board.session = session.into();
+            // An already published leaderboard keeps its session, it can be renewed if it
+            // stops working. Imported leaderboards have none, so they start using this one.
+            if let Some(pub_board) = find_pub_leaderboard(board_id)? {
                 let token = &pub_board.token;
-                save_pub_leaderboard(&token, &pub_board)?;
-
+                if pub_board.is_view_only() {
+                    let _lock = lock_pub_leaderboard(token)?;
+                    let mut pub_board = load_pub_leaderboard(token)?;
+                    if pub_board.is_view_only() {
+                        pub_board.session = session.into();
+                        save_pub_leaderboard(token, &pub_board)?;
+                    }
+                }
                 return Ok(Response::redirect(format!(
                     "{script}/leaderboard/{token}/{year}/"
                 )));
             }
 
-            loop {
-                let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
-                if !pub_leaderboard_exists(&token) {
-                    let leaderboard = PublicLeaderboard::new(&token, board_id, session);
-                    save_pub_leaderboard(&token, &leaderboard)?;
-                    return Ok(Response::redirect(format!(
-                        "{script}/leaderboard/{token}/{year}/"
-                    )));
-                }
-            }
+            let token = new_pub_token();
+            let leaderboard = PublicLeaderboard::new(&token, board_id, session);
+            save_pub_leaderboard(&token, &leaderboard)?;
+            Ok(Response::redirect(format!(
+                "{script}/leaderboard/{token}/{year}/"
+            )))
         }
     }
 }
 
-fn view_leaderboard(params: &Params) -> Result<Response> {
+fn publish_leaderboard(request: &Request, params: &Params) -> Result<Response> {
+    // /session/:session/:leaderboard/:year/publish/
+    let session = params.find("session").unwrap();
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let script = request.script();
+    let back = format!("{script}/session/{session}/{board_id}/{year}/");
+    publish(request, session, board_id, year, &back)
+}
+
+fn view_leaderboard(request: &Request, params: &Params) -> Result<Response> {
     // /session/:session/:leaderboard/:year/
     let session = params.find("session").unwrap();
     let board_id = params.find("leaderboard").unwrap();
     let year = params.find("year").unwrap();
-    let year = i32::from_str_radix(year, 10).ok_or_response(Response::not_found())?;
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    if let Some(format) = Format::from_request(request)? {
+        return Ok(export_leaderboard(
+            request,
+            session,
+            year,
+            board_id,
+            &Settings::default(),
+            format,
+        )?);
+    }
 
-    let leaderboard = render_leaderboard(session, year, board_id)?;
-    let script = get_script();
+    let leaderboard = render_leaderboard(request, session, year, board_id, &Settings::default())?;
+    let script = request.script();
 
     Ok(Response::success(
         "text/gemini",
@@ -200,6 +426,8 @@ fn view_leaderboard(params: &Params) -> Result<Response> {
 # Advent of Code Leaderboard {year}
 
 => {script}/session/{session}/{board_id}/ View a different year
+=> {script}/session/{session}/{board_id}/all/ View all years
+=> {script}/session/{session}/{board_id}/{year}/ranks/ View the ranks over time
 
 The overall scores for each person in the leaderboard.
 
@@ -213,13 +441,13 @@ The overall scores for each person in the leaderboard.
     ))
 }
 
-fn get_year(params: &Params) -> Result<Response> {
+fn get_year(request: &Request, params: &Params) -> Result<Response> {
     // /session/:session/:leaderboard/
     let session = params.find("session").unwrap();
     let leaderboard = params.find("leaderboard").unwrap();
 
-    let script = get_script();
-    let query = get_query();
+    let script = request.script();
+    let query = request.query();
     if query.is_empty() {
         Ok(Response::input("Which year would you like to view?"))
     } else {
@@ -229,40 +457,177 @@ fn get_year(params: &Params) -> Result<Response> {
     }
 }
 
-fn get_leaderboard(params: &Params) -> Result<Response> {
+fn get_leaderboard(request: &Request, params: &Params) -> Result<Response> {
     // /session/:session/
     let session = params.find("session").unwrap();
 
-    let script = get_script();
-    let query = get_query();
+    let script = request.script();
+    let query = decode(request.query()).ok_or_response(Response::bad_request())?;
     if query.is_empty() {
-        Ok(Response::input("Enter your leaderboard id"))
-    } else {
-        Ok(Response::redirect(format!(
-            "{script}/session/{session}/{query}/"
-        )))
+        return Ok(Response::input("Enter your leaderboard id"));
+    }
+    match parse_board_ids(&query) {
+        Some(board_id) => Ok(Response::redirect(format!(
+            "{script}/session/{session}/{board_id}/"
+        ))),
+        None => Ok(Response::input(INVALID_BOARD_IDS)),
     }
 }
 
-fn get_session(_params: &Params) -> Result<Response> {
+fn get_session(request: &Request, _params: &Params) -> Result<Response> {
     // /session/
 
-    let script = get_script();
-    let query = get_query();
+    let script = request.script();
+    let query = request.query();
     if query.is_empty() {
         Ok(Response::input("Enter your session key"))
     } else {
-        // Account for copying from firefox (session:"{session}")
-        let regex = Regex::new(r#""(.*)""#).unwrap();
-        let session = if let Some(captures) = regex.captures(&query) {
-            captures.get(1).unwrap().as_str()
-        } else {
-            &query
-        };
+        let session = parse_session(query);
         Ok(Response::redirect(format!("{script}/session/{session}/")))
     }
 }
 
+fn login(request: &Request, _params: &Params) -> Result<Response> {
+    // /me/login/
+    let cert = require_cert(request)?;
+
+    let script = request.script();
+    let query = decode(request.query()).ok_or_response(Response::bad_request())?;
+    if query.is_empty() {
+        Ok(Response::sensitive_input("Enter your session key"))
+    } else {
+        let session = parse_session(&query);
+
+        // Make sure the session is valid before linking it, looking up its member id on the way
+        let member = match fetch_member_id(session) {
+            Ok(member) => member,
+            Err(FetchError::Unauthorized) => {
+                return Ok(Response::sensitive_input(
+                    "That session key isn't valid, enter your session key again",
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        save_identity(&Identity {
+            member: Some(member),
+            ..Identity::new(&cert.hash, session)
+        })?;
+        Ok(Response::redirect(format!("{script}/me/")))
+    }
+}
+
+fn logout(request: &Request, _params: &Params) -> Result<Response> {
+    // /me/logout/
+    let cert = require_cert(request)?;
+    delete_identity(&cert.hash)?;
+
+    let script = request.script();
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# Logged out
+
+Your session key is no longer linked to your certificate.
+
+=> {script}/ Back to the capsule
+"
+        ),
+    ))
+}
+
+fn publish_my_leaderboard(request: &Request, params: &Params) -> Result<Response> {
+    // /me/:leaderboard/:year/publish/
+    let identity = require_identity(request)?;
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let script = request.script();
+    let back = format!("{script}/me/{board_id}/{year}/");
+    publish(request, &identity.session, board_id, year, &back)
+}
+
+fn view_my_leaderboard(request: &Request, params: &Params) -> Result<Response> {
+    // /me/:leaderboard/:year/
+    let identity = require_identity(request)?;
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    if let Some(format) = Format::from_request(request)? {
+        return Ok(export_leaderboard(
+            request,
+            &identity.session,
+            year,
+            board_id,
+            &Settings::default(),
+            format,
+        )?);
+    }
+
+    let leaderboard = render_leaderboard(
+        request,
+        &identity.session,
+        year,
+        board_id,
+        &Settings::default(),
+    )?;
+    let script = request.script();
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# Advent of Code Leaderboard {year}
+
+=> {script}/me/{board_id}/ View a different year
+=> {script}/me/{board_id}/all/ View all years
+=> {script}/me/{board_id}/{year}/ranks/ View the ranks over time
+
+The overall scores for each person in the leaderboard.
+
+{leaderboard}
+
+## This leaderboard is not public Would you like to share it?
+
+=> {script}/me/{board_id}/{year}/publish/ Make this leaderboard public
+"
+        ),
+    ))
+}
+
+fn get_my_year(request: &Request, params: &Params) -> Result<Response> {
+    // /me/:leaderboard/
+    require_identity(request)?;
+    let leaderboard = params.find("leaderboard").unwrap();
+
+    let script = request.script();
+    let query = request.query();
+    if query.is_empty() {
+        Ok(Response::input("Which year would you like to view?"))
+    } else {
+        Ok(Response::redirect(format!(
+            "{script}/me/{leaderboard}/{query}/"
+        )))
+    }
+}
+
+fn get_my_leaderboard(request: &Request, _params: &Params) -> Result<Response> {
+    // /me/
+    require_identity(request)?;
+
+    let script = request.script();
+    let query = decode(request.query()).ok_or_response(Response::bad_request())?;
+    if query.is_empty() {
+        return Ok(Response::input("Enter your leaderboard id"));
+    }
+    match parse_board_ids(&query) {
+        Some(board_id) => Ok(Response::redirect(format!("{script}/me/{board_id}/"))),
+        None => Ok(Response::input(INVALID_BOARD_IDS)),
+    }
+}
+
 pub fn add_routes(router: &mut Router<&FnRoute>) {
     router.add("/session", &get_session);
     router.add("/session/:session", &get_leaderboard);
@@ -272,8 +637,23 @@ pub fn add_routes(router: &mut Router<&FnRoute>) {
         &publish_leaderboard,
     );
     router.add("/session/:session/:leaderboard/:year", &view_leaderboard);
+    router.add("/me", &get_my_leaderboard);
+    router.add("/me/login", &login);
+    router.add("/me/logout", &logout);
+    router.add("/me/:leaderboard", &get_my_year);
+    router.add("/me/:leaderboard/:year/publish", &publish_my_leaderboard);
+    router.add("/me/:leaderboard/:year", &view_my_leaderboard);
     router.add("/leaderboard", &get_pub_leaderboard);
     router.add("/leaderboard/:leaderboard", &get_pub_year);
+    router.add(
+        "/leaderboard/:leaderboard/:year/scoring",
+        &select_pub_scheme,
+    );
+    router.add("/leaderboard/:leaderboard/:year/start", &list_pub_starts);
+    router.add(
+        "/leaderboard/:leaderboard/:year/start/:member",
+        &set_pub_start,
+    );
     router.add(
         "/leaderboard/:leaderboard/:year/renew",
         &renew_pub_leaderboard,
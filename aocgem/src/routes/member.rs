@@ -0,0 +1,111 @@
+use route_recognizer::{Params, Router};
+
+use crate::{
+    cgi::{OkResponse, Request, Response, Result},
+    fetch::{get_leaderboard, load_pub_leaderboard},
+    render::{
+        member::{render_member, render_member_years},
+        years::load_years,
+    },
+};
+
+use super::{require_identity, FnRoute};
+
+/// Render the member's page, `base` is the leaderboard's path without the year
+fn member_page(
+    session: &str,
+    board_id: &str,
+    year: i32,
+    member: &str,
+    base: &str,
+) -> Result<Response> {
+    let leaderboard = get_leaderboard(session, year, board_id)?.leaderboard;
+    let profile = match render_member(&leaderboard, year, member) {
+        Some(profile) => profile,
+        None => return Ok(Response::not_found()),
+    };
+    let name = &leaderboard.members[member].name;
+
+    // Only the years the member was part of, the current year is already loaded
+    let years = load_years(session, board_id)?.loaded;
+    let history = render_member_years(&years, member).unwrap_or_default();
+    let links = years
+        .iter()
+        .filter(|(other, leaderboard)| *other != year && leaderboard.members.contains_key(member))
+        .map(|(other, _)| format!("=> {base}/{other}/member/{member}/ {other}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# {name}, Advent of Code {year}
+
+=> {base}/{year}/ Back to the leaderboard
+{profile}
+## Every year
+
+The member's rank, local score, stars and average time for each star in the years they were part of the leaderboard. Years that couldn't be loaded are left out.
+
+```member years
+{history}
+```
+
+{links}
+"
+        ),
+    ))
+}
+
+fn view_session_member(request: &Request, params: &Params) -> Result<Response> {
+    // /session/:session/:leaderboard/:year/member/:member/
+    let session = params.find("session").unwrap();
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+    let member = params.find("member").unwrap();
+
+    let script = request.script();
+    let base = format!("{script}/session/{session}/{board_id}");
+    member_page(session, board_id, year, member, &base)
+}
+
+fn view_my_member(request: &Request, params: &Params) -> Result<Response> {
+    // /me/:leaderboard/:year/member/:member/
+    let identity = require_identity(request)?;
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+    let member = params.find("member").unwrap();
+
+    let script = request.script();
+    let base = format!("{script}/me/{board_id}");
+    member_page(&identity.session, board_id, year, member, &base)
+}
+
+fn view_pub_member(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/member/:member/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+    let member = params.find("member").unwrap();
+
+    let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+
+    let script = request.script();
+    let base = format!("{script}/leaderboard/{board_id}");
+    member_page(&pub_board.session, &pub_board.id, year, member, &base)
+}
+
+pub fn add_routes(router: &mut Router<&FnRoute>) {
+    router.add(
+        "/session/:session/:leaderboard/:year/member/:member",
+        &view_session_member,
+    );
+    router.add("/me/:leaderboard/:year/member/:member", &view_my_member);
+    router.add(
+        "/leaderboard/:leaderboard/:year/member/:member",
+        &view_pub_member,
+    );
+}
@@ -0,0 +1,71 @@
+use route_recognizer::{Params, Router};
+
+use crate::{
+    cgi::{OkResponse, Request, Response, Result},
+    fetch::load_pub_leaderboard,
+    render::ranks::render_ranks,
+};
+
+use super::{require_identity, FnRoute};
+
+fn ranks_page(year: i32, back: &str, ranks: &str) -> Response {
+    Response::success(
+        "text/gemini",
+        format!(
+            "
+# Advent of Code Leaderboard {year}, Ranks
+
+=> {back} Back to the leaderboard
+{ranks}"
+        ),
+    )
+}
+
+fn view_session_ranks(request: &Request, params: &Params) -> Result<Response> {
+    // /session/:session/:leaderboard/:year/ranks/
+    let session = params.find("session").unwrap();
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let ranks = render_ranks(session, year, board_id)?;
+    let script = request.script();
+    let back = format!("{script}/session/{session}/{board_id}/{year}/");
+    Ok(ranks_page(year, &back, &ranks))
+}
+
+fn view_my_ranks(request: &Request, params: &Params) -> Result<Response> {
+    // /me/:leaderboard/:year/ranks/
+    let identity = require_identity(request)?;
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let ranks = render_ranks(&identity.session, year, board_id)?;
+    let script = request.script();
+    let back = format!("{script}/me/{board_id}/{year}/");
+    Ok(ranks_page(year, &back, &ranks))
+}
+
+fn view_pub_ranks(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/ranks/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+
+    let ranks = render_ranks(&pub_board.session, year, &pub_board.id)?;
+    let script = request.script();
+    let back = format!("{script}/leaderboard/{board_id}/{year}/");
+    Ok(ranks_page(year, &back, &ranks))
+}
+
+pub fn add_routes(router: &mut Router<&FnRoute>) {
+    router.add(
+        "/session/:session/:leaderboard/:year/ranks",
+        &view_session_ranks,
+    );
+    router.add("/me/:leaderboard/:year/ranks", &view_my_ranks);
+    router.add("/leaderboard/:leaderboard/:year/ranks", &view_pub_ranks);
+}
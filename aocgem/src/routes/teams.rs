@@ -0,0 +1,128 @@
+use route_recognizer::{Params, Router};
+use urlencoding::decode;
+
+use crate::{
+    cgi::{OkResponse, Request, Response, Result},
+    fetch::{load_pub_leaderboard, lock_pub_leaderboard, save_pub_leaderboard},
+    leaderboard::parse_team,
+    render::teams::{render_team, render_teams},
+};
+
+use super::{require_owner, FnRoute};
+
+fn view_pub_teams(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/teams/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+
+    let script = request.script();
+    let base = format!("{script}/leaderboard/{board_id}/{year}/teams");
+    let teams = render_teams(
+        &pub_board.session,
+        year,
+        &pub_board.id,
+        &pub_board.settings.teams,
+        &base,
+    )?;
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# Advent of Code Leaderboard {year}, Teams
+
+=> {script}/leaderboard/{board_id}/{year}/ Back to the leaderboard
+{teams}"
+        ),
+    ))
+}
+
+fn edit_pub_team(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/teams/edit/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+
+    let _lock = lock_pub_leaderboard(board_id)?;
+    let mut pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+    require_owner(request, &pub_board)?;
+
+    let script = request.script();
+    let query = request.query();
+    let query = decode(query).ok_or_response(Response::bad_request())?;
+    if query.is_empty() {
+        return Ok(Response::input(
+            "Enter the team's name and member ids (Backend: 123, 456)",
+        ));
+    }
+    match parse_team(&query) {
+        // The team's page would be the page for editing teams
+        Some((name, _)) if name == "edit" => {
+            return Ok(Response::input(
+                "A team can't be called edit, use a different name",
+            ))
+        }
+        Some((name, ids)) if ids.is_empty() => {
+            pub_board.settings.teams.remove(&name);
+        }
+        Some((name, ids)) => {
+            pub_board.settings.teams.insert(name, ids);
+        }
+        None => {
+            return Ok(Response::input(
+                "Invalid team, use the name followed by the member ids (Backend: 123, 456)",
+            ))
+        }
+    }
+    save_pub_leaderboard(board_id, &pub_board)?;
+    Ok(Response::redirect(format!(
+        "{script}/leaderboard/{board_id}/{year}/teams/"
+    )))
+}
+
+fn view_pub_team(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/:year/teams/:team/
+    let board_id = params.find("leaderboard").unwrap();
+    let year = params.find("year").unwrap();
+    let year = year.parse::<i32>().ok_or_response(Response::not_found())?;
+    let team = params.find("team").unwrap();
+    let team = decode(team).ok_or_response(Response::bad_request())?;
+
+    let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+    let ids = match pub_board.settings.teams.get(team.as_ref()) {
+        Some(ids) => ids,
+        None => return Ok(Response::not_found()),
+    };
+
+    let members = render_team(
+        &pub_board.session,
+        year,
+        &pub_board.id,
+        ids,
+        &pub_board.settings,
+    )?;
+    let script = request.script();
+
+    Ok(Response::success(
+        "text/gemini",
+        format!(
+            "
+# Advent of Code Leaderboard {year}, {team}
+
+=> {script}/leaderboard/{board_id}/{year}/teams/ Back to the teams
+{members}"
+        ),
+    ))
+}
+
+pub fn add_routes(router: &mut Router<&FnRoute>) {
+    router.add("/leaderboard/:leaderboard/:year/teams", &view_pub_teams);
+    router.add("/leaderboard/:leaderboard/:year/teams/edit", &edit_pub_team);
+    router.add(
+        "/leaderboard/:leaderboard/:year/teams/:team",
+        &view_pub_team,
+    );
+}
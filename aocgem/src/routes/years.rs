@@ -0,0 +1,62 @@
+use route_recognizer::{Params, Router};
+
+use crate::{
+    cgi::{OkResponse, Request, Response, Result},
+    fetch::load_pub_leaderboard,
+    render::years::render_years,
+};
+
+use super::{require_identity, FnRoute};
+
+fn years_page(back: &str, years: &str) -> Response {
+    Response::success(
+        "text/gemini",
+        format!(
+            "
+# Advent of Code Leaderboard, All Years
+
+=> {back} View a single year
+{years}"
+        ),
+    )
+}
+
+fn view_session_years(request: &Request, params: &Params) -> Result<Response> {
+    // /session/:session/:leaderboard/all/
+    let session = params.find("session").unwrap();
+    let board_id = params.find("leaderboard").unwrap();
+
+    let years = render_years(session, board_id)?;
+    let script = request.script();
+    let back = format!("{script}/session/{session}/{board_id}/");
+    Ok(years_page(&back, &years))
+}
+
+fn view_my_years(request: &Request, params: &Params) -> Result<Response> {
+    // /me/:leaderboard/all/
+    let identity = require_identity(request)?;
+    let board_id = params.find("leaderboard").unwrap();
+
+    let years = render_years(&identity.session, board_id)?;
+    let script = request.script();
+    let back = format!("{script}/me/{board_id}/");
+    Ok(years_page(&back, &years))
+}
+
+fn view_pub_years(request: &Request, params: &Params) -> Result<Response> {
+    // /leaderboard/:leaderboard/all/
+    let board_id = params.find("leaderboard").unwrap();
+
+    let pub_board = load_pub_leaderboard(board_id).ok_or_response(Response::not_found())?;
+
+    let years = render_years(&pub_board.session, &pub_board.id)?;
+    let script = request.script();
+    let back = format!("{script}/leaderboard/{board_id}/");
+    Ok(years_page(&back, &years))
+}
+
+pub fn add_routes(router: &mut Router<&FnRoute>) {
+    router.add("/session/:session/:leaderboard/all", &view_session_years);
+    router.add("/me/:leaderboard/all", &view_my_years);
+    router.add("/leaderboard/:leaderboard/all", &view_pub_years);
+}
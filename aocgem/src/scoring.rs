@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::leaderboard::{Leaderboard, Member};
+
+/// How the members of a public leaderboard are ranked
+///
+/// There is no scheme for the time since a member opened a day. When a day
+/// was opened isn't in the leaderboard, and estimating it from the first star
+/// only leaves the time between the stars, which is what `Delta` ranks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scheme {
+    /// The local score from adventofcode.com
+    #[default]
+    Aoc,
+    /// Points for each day, the fastest to go from the first star to the second gets the most
+    ///
+    /// Leaderboards that were ranked by the time since opening a day use this instead.
+    #[serde(alias = "opened")]
+    Delta,
+    /// The median time to finish a day after it unlocked
+    Median,
+}
+
+impl Scheme {
+    pub const ALL: [Scheme; 3] = [Scheme::Aoc, Scheme::Delta, Scheme::Median];
+
+    /// The name used in urls and the config
+    pub fn key(&self) -> &'static str {
+        match self {
+            Scheme::Aoc => "aoc",
+            Scheme::Delta => "delta",
+            Scheme::Median => "median",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scheme| scheme.key() == key)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scheme::Aoc => "local score",
+            Scheme::Delta => "part 2 delta",
+            Scheme::Median => "median time",
+        }
+    }
+}
+
+/// A member's score in a scheme
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Score {
+    Points(i32),
+    /// A time for the days the member finished
+    Time {
+        days: i32,
+        time: Duration,
+    },
+}
+
+impl Score {
+    /// Lower keys rank higher
+    ///
+    /// Finishing more days always beats a better time.
+    pub fn key(&self) -> (i64, i64) {
+        match self {
+            Score::Points(points) => (-(*points as i64), 0),
+            Score::Time { days, time } => (-(*days as i64), time.num_seconds()),
+        }
+    }
+}
+
+/// A star earned by a member
+struct Star {
+    member: i32,
+    get_star_ts: i64,
+    star_index: i32,
+}
+
+/// Every star earned for each day and part
+fn stars(leaderboard: &Leaderboard) -> HashMap<(u32, u32), Vec<Star>> {
+    let mut stars: HashMap<(u32, u32), Vec<Star>> = HashMap::new();
+    for member in leaderboard.members.values() {
+        for (day, parts) in &member.completion_day_level {
+            let day = match day.parse::<u32>() {
+                Ok(day) => day,
+                Err(_) => continue,
+            };
+            for (part, completion) in parts {
+                let part = match part.parse::<u32>() {
+                    Ok(part) => part,
+                    Err(_) => continue,
+                };
+                stars.entry((day, part)).or_default().push(Star {
+                    member: member.id,
+                    get_star_ts: completion.get_star_ts,
+                    star_index: completion.star_index,
+                });
+            }
+        }
+    }
+    for stars in stars.values_mut() {
+        stars.sort_unstable_by_key(|star| (star.get_star_ts, star.star_index));
+    }
+    stars
+}
+
+/// The points each member earned for every day and part, counting only the stars earned before `until`
+///
+/// The first member to get a star earns as many points as there are members,
+/// the second one less, and so on. Stars earned at the same second go to
+/// whoever has the lower `star_index`.
+pub fn points(leaderboard: &Leaderboard, until: i64) -> HashMap<i32, HashMap<(u32, u32), i32>> {
+    let count = leaderboard.members.len() as i32;
+    let mut points: HashMap<i32, HashMap<(u32, u32), i32>> = leaderboard
+        .members
+        .values()
+        .map(|member| (member.id, HashMap::new()))
+        .collect();
+
+    for (level, stars) in stars(leaderboard) {
+        for (i, star) in stars.iter().enumerate() {
+            if star.get_star_ts >= until {
+                break;
+            }
+            points
+                .entry(star.member)
+                .or_default()
+                .insert(level, count - i as i32);
+        }
+    }
+    points
+}
+
+/// Reconstruct the local score of each member counting only the stars earned before `until`
+pub fn local_scores(leaderboard: &Leaderboard, until: i64) -> HashMap<i32, i32> {
+    points(leaderboard, until)
+        .into_iter()
+        .map(|(id, points)| (id, points.values().sum()))
+        .collect()
+}
+
+/// The time between both stars of every day the member finished
+fn deltas(member: &Member) -> HashMap<u32, (i64, i32)> {
+    member
+        .completion_day_level
+        .iter()
+        .filter_map(|(day, parts)| {
+            let first = parts.get("1")?;
+            let second = parts.get("2")?;
+            Some((
+                day.parse::<u32>().ok()?,
+                (second.get_star_ts - first.get_star_ts, second.star_index),
+            ))
+        })
+        .collect()
+}
+
+/// The middle of the times, or zero when there are none
+pub fn median(mut times: Vec<Duration>) -> Duration {
+    times.sort_unstable();
+    let middle = times.len() / 2;
+    if times.is_empty() {
+        Duration::zero()
+    } else if times.len().is_multiple_of(2) {
+        (times[middle - 1] + times[middle]) / 2
+    } else {
+        times[middle]
+    }
+}
+
+/// Score every member of the leaderboard with a scheme
+pub fn scheme_scores(leaderboard: &Leaderboard, year: i32, scheme: Scheme) -> HashMap<i32, Score> {
+    let members = leaderboard.members.values();
+    match scheme {
+        Scheme::Aoc => members
+            .map(|member| (member.id, Score::Points(member.local_score)))
+            .collect(),
+        Scheme::Delta => {
+            let count = leaderboard.members.len() as i32;
+            let mut days: HashMap<u32, Vec<(i64, i32, i32)>> = HashMap::new();
+            for member in members {
+                for (day, (delta, star_index)) in deltas(member) {
+                    days.entry(day)
+                        .or_default()
+                        .push((delta, star_index, member.id));
+                }
+            }
+            let mut scores: HashMap<i32, Score> = leaderboard
+                .members
+                .values()
+                .map(|member| (member.id, Score::Points(0)))
+                .collect();
+            for mut deltas in days.into_values() {
+                deltas.sort_unstable();
+                for (i, (_, _, id)) in deltas.into_iter().enumerate() {
+                    if let Some(Score::Points(points)) = scores.get_mut(&id) {
+                        *points += count - i as i32;
+                    }
+                }
+            }
+            scores
+        }
+        Scheme::Median => members
+            .map(|member| {
+                let times: Vec<_> = member
+                    .completion_times(year)
+                    .into_values()
+                    .filter_map(|(a, b)| Some(a? + b?))
+                    .collect();
+                let days = times.len() as i32;
+                (
+                    member.id,
+                    Score::Time {
+                        days,
+                        time: median(times),
+                    },
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Order the members by their local score before `until`
+///
+/// Ties go to whoever got their last star first, like on adventofcode.com.
+pub fn ranks(leaderboard: &Leaderboard, until: i64) -> Vec<i32> {
+    let scores = local_scores(leaderboard, until);
+    let mut members: Vec<_> = leaderboard
+        .members
+        .values()
+        .map(|member| {
+            let last_star = member
+                .completion_day_level
+                .values()
+                .flat_map(|parts| parts.values())
+                .map(|completion| completion.get_star_ts)
+                .filter(|ts| *ts < until)
+                .max()
+                .unwrap_or(i64::MAX);
+            (-scores[&member.id], last_star, member.id)
+        })
+        .collect();
+    members.sort_unstable();
+    members.into_iter().map(|(_, _, id)| id).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::leaderboard::test::{sample_board, sample_member};
+
+    fn leaderboard() -> Leaderboard {
+        // Alice gets day 1 first, Bob gets both parts of day 2 first
+        sample_board(vec![
+            sample_member(
+                1,
+                "Alice",
+                &[(1, 1, 100, 1), (1, 2, 200, 3), (2, 1, 1100, 7)],
+            ),
+            sample_member(
+                2,
+                "Bob",
+                &[
+                    (1, 1, 150, 2),
+                    (1, 2, 250, 4),
+                    (2, 1, 1000, 5),
+                    (2, 2, 1050, 6),
+                ],
+            ),
+        ])
+    }
+
+    /// Three members whose local scores are what adventofcode.com reports
+    fn scored() -> Leaderboard {
+        let mut a = sample_member(
+            1,
+            "A",
+            &[
+                (1, 1, 100, 1),
+                (1, 2, 250, 4),
+                (2, 1, 1100, 7),
+                (2, 2, 1200, 9),
+            ],
+        );
+        a.local_score = 9;
+        let mut b = sample_member(2, "B", &[(1, 1, 150, 2), (1, 2, 200, 3)]);
+        b.local_score = 5;
+        let mut c = sample_member(3, "C", &[(1, 1, 300, 5), (2, 1, 1000, 6), (2, 2, 1200, 8)]);
+        c.local_score = 7;
+        sample_board(vec![a, b, c])
+    }
+
+    #[test]
+    fn test_matches_local_score() {
+        let leaderboard = scored();
+        let scores = local_scores(&leaderboard, i64::MAX);
+        for member in leaderboard.members.values() {
+            assert_eq!(scores[&member.id], member.local_score, "{}", member.name);
+        }
+    }
+
+    #[test]
+    fn test_points() {
+        let points = points(&scored(), i64::MAX);
+
+        assert_eq!(points[&1][&(1, 1)], 3);
+        assert_eq!(points[&2][&(1, 1)], 2);
+        assert_eq!(points[&3][&(1, 1)], 1);
+        assert_eq!(points[&2][&(1, 2)], 3);
+        assert!(!points[&3].contains_key(&(1, 2)));
+        // Both got the star at the same second, the lower star_index wins
+        assert_eq!(points[&3][&(2, 2)], 3);
+        assert_eq!(points[&1][&(2, 2)], 2);
+    }
+
+    #[test]
+    fn test_schemes() {
+        let leaderboard = scored();
+
+        // A took 150s on day 1 and 100s on day 2, B took 50s and C 200s
+        let delta = scheme_scores(&leaderboard, 2022, Scheme::Delta);
+        assert_eq!(delta[&1], Score::Points(2 + 3));
+        assert_eq!(delta[&2], Score::Points(3));
+        assert_eq!(delta[&3], Score::Points(2));
+
+        let median = scheme_scores(&leaderboard, 2022, Scheme::Median);
+        assert_eq!(median[&2].key().0, -1);
+        assert!(median[&1].key() < median[&2].key());
+
+        let aoc = scheme_scores(&leaderboard, 2022, Scheme::Aoc);
+        assert_eq!(aoc[&1], Score::Points(9));
+    }
+
+    #[test]
+    fn test_opened_is_delta() {
+        let scheme: Scheme = serde_json::from_str(r#""opened""#).unwrap();
+        assert_eq!(scheme, Scheme::Delta);
+        assert_eq!(serde_json::to_string(&scheme).unwrap(), r#""delta""#);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(
+            median(vec![Duration::seconds(30), Duration::seconds(10)]),
+            Duration::seconds(20)
+        );
+        assert_eq!(
+            median(vec![
+                Duration::seconds(30),
+                Duration::seconds(10),
+                Duration::seconds(500)
+            ]),
+            Duration::seconds(30)
+        );
+    }
+
+    #[test]
+    fn test_ranks_over_time() {
+        let leaderboard = leaderboard();
+
+        assert_eq!(ranks(&leaderboard, 500), vec![1, 2]);
+        assert_eq!(local_scores(&leaderboard, 500)[&1], 4);
+        assert_eq!(ranks(&leaderboard, 2000), vec![2, 1]);
+        assert_eq!(local_scores(&leaderboard, 2000)[&2], 6);
+    }
+}
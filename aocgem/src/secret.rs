@@ -0,0 +1,190 @@
+use std::{
+    env,
+    error::Error,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+
+use crate::fetch::get_cache_path;
+
+/// Marks a value that has been encrypted, so plaintext records can still be read
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+fn get_key_path() -> PathBuf {
+    match env::var("AOCGEM_KEY_FILE") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => get_cache_path().join("secret.key"),
+    }
+}
+
+fn decode_key(encoded: &str) -> Result<Key, Box<dyn Error>> {
+    let bytes = STANDARD.decode(encoded.trim())?;
+    if bytes.len() != 32 {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The secret key must be 32 bytes",
+        )));
+    }
+    Ok(*Key::from_slice(&bytes))
+}
+
+/// Load the server key used to encrypt session keys
+///
+/// The key is read from the `AOCGEM_KEY` environment variable, or the file at
+/// `AOCGEM_KEY_FILE` (defaults to `secret.key` in the data dir). If neither
+/// exist, a new key is generated and saved to the file.
+pub fn load_key() -> Result<Key, Box<dyn Error>> {
+    if let Ok(encoded) = env::var("AOCGEM_KEY") {
+        return decode_key(&encoded);
+    }
+
+    load_key_file(&get_key_path())
+}
+
+fn read_key(path: &Path) -> Result<Key, Box<dyn Error>> {
+    let mut f = File::open(path)?;
+    let mut contents = String::new();
+    f.read_to_string(&mut contents)?;
+    decode_key(&contents)
+}
+
+/// Read the key file, or create it with a new key
+///
+/// The new key is written to a temporary file and then linked into place,
+/// which fails if the file already exists. If another process created the key
+/// first, its key is used instead, so only one key is ever handed out.
+fn load_key_file(path: &Path) -> Result<Key, Box<dyn Error>> {
+    if path.exists() {
+        return read_key(path);
+    }
+
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension(format!("key.{:016x}.tmp", rand::random::<u64>()));
+    let mut f = File::create(&tmp)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        f.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    f.write_all(STANDARD.encode(key).as_bytes())?;
+    f.sync_all()?;
+
+    let linked = fs::hard_link(&tmp, path);
+    fs::remove_file(&tmp)?;
+    match linked {
+        Ok(()) => Ok(key),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => read_key(path),
+        Err(err) => Err(Box::new(err)),
+    }
+}
+
+#[inline]
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+pub fn encrypt(key: &Key, value: &str) -> Result<String, Box<dyn Error>> {
+    let cipher = ChaCha20Poly1305::new(key);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let encrypted = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Could not encrypt value"))?;
+
+    let mut bytes = nonce.to_vec();
+    bytes.extend(encrypted);
+    Ok(format!("{PREFIX}{}", STANDARD.encode(bytes)))
+}
+
+/// Decrypt a value, plaintext values are returned as they are
+pub fn decrypt(key: &Key, value: &str) -> Result<String, Box<dyn Error>> {
+    let encoded = match value.strip_prefix(PREFIX) {
+        Some(encoded) => encoded,
+        None => return Ok(value.to_owned()),
+    };
+    let bytes = STANDARD.decode(encoded)?;
+    if bytes.len() < NONCE_LEN {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Encrypted value is too short",
+        )));
+    }
+    let (nonce, encrypted) = bytes.split_at(NONCE_LEN);
+
+    let cipher = ChaCha20Poly1305::new(key);
+    let decrypted = cipher
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Could not decrypt value"))?;
+    Ok(String::from_utf8(decrypted)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        let encrypted = encrypt(&key, "my session").unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("my session"));
+        assert_eq!(decrypt(&key, &encrypted).unwrap(), "my session");
+    }
+
+    #[test]
+    fn test_plaintext() {
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+
+        assert!(!is_encrypted("my session"));
+        assert_eq!(decrypt(&key, "my session").unwrap(), "my session");
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        let other = ChaCha20Poly1305::generate_key(&mut OsRng);
+        let encrypted = encrypt(&key, "my session").unwrap();
+
+        assert!(decrypt(&other, &encrypted).is_err());
+    }
+
+    #[test]
+    fn test_key_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("secret.key");
+
+        let key = load_key_file(&path).unwrap();
+        assert_eq!(load_key_file(&path).unwrap(), key);
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_key_file_race() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("secret.key");
+
+        let keys: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || load_key_file(&path).unwrap())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect();
+
+        let key = read_key(&path).unwrap();
+        assert!(keys.iter().all(|k| *k == key));
+    }
+}
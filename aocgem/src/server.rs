@@ -0,0 +1,304 @@
+use std::{
+    env,
+    error::Error,
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use rustls::{
+    server::{ClientCertVerified, ClientCertVerifier},
+    Certificate, DistinguishedNames, PrivateKey, ServerConfig, ServerConnection, StreamOwned,
+};
+use rustls_pemfile::Item;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    cgi::{ClientCert, Request, Response},
+    fetch::get_cache_path,
+    handle,
+};
+
+/// The longest request line allowed by the gemini spec (1024 bytes + CRLF)
+const MAX_REQUEST: usize = 1026;
+
+pub struct ServerOptions {
+    pub addr: String,
+    pub hostname: String,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl ServerOptions {
+    /// Build the server options from the command line arguments
+    ///
+    /// Anything not given on the command line will fall back to the
+    /// `GEMINI_ADDR`, `GEMINI_HOST`, `GEMINI_CERT`, and `GEMINI_KEY`
+    /// environment variables, then to the defaults.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        let mut options = Self {
+            addr: env::var("GEMINI_ADDR").unwrap_or_else(|_| "0.0.0.0:1965".into()),
+            hostname: env::var("GEMINI_HOST").unwrap_or_else(|_| "localhost".into()),
+            cert: env::var("GEMINI_CERT")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| get_cache_path().join("cert.pem")),
+            key: env::var("GEMINI_KEY")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| get_cache_path().join("key.pem")),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Missing value for {arg}"),
+                    )
+                })
+            };
+            match arg.as_str() {
+                "--addr" => options.addr = value()?,
+                "--host" => options.hostname = value()?,
+                "--cert" => options.cert = value()?.into(),
+                "--key" => options.key = value()?.into(),
+                _ => {
+                    return Err(Box::new(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Unknown argument {arg}"),
+                    )))
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// The port the server listens on, which requests have to be for
+    fn port(&self) -> u16 {
+        self.addr
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse().ok())
+            .unwrap_or(1965)
+    }
+}
+
+/// Create a self-signed certificate for the hostname if there isn't one already
+fn ensure_certificate(options: &ServerOptions) -> Result<(), Box<dyn Error>> {
+    if options.cert.exists() && options.key.exists() {
+        return Ok(());
+    }
+    eprintln!(
+        "Generating a self-signed certificate for {} in {}",
+        options.hostname,
+        options.cert.display()
+    );
+
+    let cert = rcgen::generate_simple_self_signed(vec![options.hostname.clone()])?;
+    for path in [&options.cert, &options.key] {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    File::create(&options.cert)?.write_all(cert.serialize_pem()?.as_bytes())?;
+    let mut key = File::create(&options.key)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        key.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    key.write_all(cert.serialize_private_key_pem().as_bytes())?;
+    Ok(())
+}
+
+/// Ask for a client certificate, but accept any certificate given
+///
+/// Gemini client certificates are almost always self-signed, so they are only
+/// used to identify a client. The handshake still makes sure that the client
+/// owns the certificate.
+struct AnyClientCert;
+
+impl ClientCertVerifier for AnyClientCert {
+    fn client_auth_mandatory(&self) -> Option<bool> {
+        Some(false)
+    }
+
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        Some(Vec::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+/// Get the fingerprint of a certificate in the same format as `TLS_CLIENT_HASH`
+pub fn cert_fingerprint(cert: &Certificate) -> String {
+    let digest = Sha256::digest(&cert.0);
+    let hex: String = digest.iter().map(|b| format!("{b:02X}")).collect();
+    format!("SHA256:{hex}")
+}
+
+fn load_config(options: &ServerOptions) -> Result<ServerConfig, Box<dyn Error>> {
+    ensure_certificate(options)?;
+
+    let mut reader = BufReader::new(File::open(&options.cert)?);
+    let certs = rustls_pemfile::certs(&mut reader)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+
+    let mut reader = BufReader::new(File::open(&options.key)?);
+    let key = rustls_pemfile::read_all(&mut reader)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No private key found"))?;
+
+    Ok(ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(Arc::new(AnyClientCert))
+        .with_single_cert(certs, key)?)
+}
+
+/// Read the request line, stopping at CRLF
+fn read_request(stream: &mut impl Read) -> Result<String, Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    let mut byte = [0; 1];
+    while !buffer.ends_with(b"\r\n") {
+        if buffer.len() >= MAX_REQUEST {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Request too long",
+            )));
+        }
+        if stream.read(&mut byte)? == 0 {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed before the request was finished",
+            )));
+        }
+        buffer.push(byte[0]);
+    }
+    buffer.truncate(buffer.len() - 2);
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// Whether the url is for this server, and not one it would have to proxy to
+///
+/// A url without a port is for the default gemini port.
+fn is_served(url: &Url, hostname: &str, port: u16) -> bool {
+    url.host_str()
+        .is_some_and(|host| host.eq_ignore_ascii_case(hostname))
+        && url.port().unwrap_or(1965) == port
+}
+
+fn handle_connection(
+    config: Arc<ServerConfig>,
+    options: &ServerOptions,
+    stream: TcpStream,
+) -> Result<(), Box<dyn Error>> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let connection = ServerConnection::new(config)?;
+    let mut stream = StreamOwned::new(connection, stream);
+
+    let response = match read_request(&mut stream)
+        .ok()
+        .and_then(|request| Url::parse(&request).ok())
+    {
+        Some(url)
+            if url.scheme() == "gemini" && is_served(&url, &options.hostname, options.port()) =>
+        {
+            let cert = stream
+                .conn
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| ClientCert {
+                    hash: cert_fingerprint(cert),
+                    name: None,
+                });
+            let request = Request::new(&url, "").with_cert(cert);
+            handle(&request).to_string().into_bytes()
+        }
+        Some(url) if url.scheme() == "gemini" => {
+            Response::proxy_refused(format!("Only requests for {} are served", options.hostname))
+                .to_string()
+                .into_bytes()
+        }
+        Some(_) => Response::proxy_refused("Only gemini requests are supported")
+            .to_string()
+            .into_bytes(),
+        None => Response::bad_request().to_string().into_bytes(),
+    };
+
+    stream.write_all(&response)?;
+    stream.flush()?;
+    stream.conn.send_close_notify();
+    stream.flush()?;
+    Ok(())
+}
+
+/// Listen for gemini requests until the process is killed
+pub fn serve(options: ServerOptions) -> Result<(), Box<dyn Error>> {
+    let config = Arc::new(load_config(&options)?);
+    let options = Arc::new(options);
+    let listener = TcpListener::bind(&options.addr)?;
+    eprintln!("Listening on gemini://{}", options.addr);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("{err}");
+                continue;
+            }
+        };
+        let config = config.clone();
+        let options = options.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle_connection(config, &options, stream) {
+                eprintln!("{err}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_served() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        assert!(is_served(&url("gemini://localhost/"), "localhost", 1965));
+        assert!(is_served(
+            &url("gemini://LocalHost:1965/me/"),
+            "localhost",
+            1965
+        ));
+        assert!(is_served(
+            &url("gemini://localhost:1966/"),
+            "localhost",
+            1966
+        ));
+        assert!(!is_served(
+            &url("gemini://localhost:1966/"),
+            "localhost",
+            1965
+        ));
+        assert!(!is_served(&url("gemini://example.com/"), "localhost", 1965));
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{Read, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::lock::FileLock;
+
+/// The current time in unix seconds, as state files store it
+pub fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+/// A json file of state shared by every process, like the rate limiter's buckets
+///
+/// Changes should be made while holding the lock, which is kept in a `.lock`
+/// file next to the state.
+pub struct StateFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+}
+
+impl StateFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            lock_path: path.with_extension("lock"),
+            path,
+        }
+    }
+
+    /// Hold an exclusive lock on the state until the lock is dropped
+    pub fn lock(&self) -> Result<FileLock, Box<dyn Error>> {
+        FileLock::lock(&self.lock_path)
+    }
+
+    /// Read the state, `None` if it is missing or corrupted
+    pub fn read<T: DeserializeOwned>(&self) -> Option<T> {
+        let mut f = File::open(&self.path).ok()?;
+        let mut contents = String::new();
+        f.read_to_string(&mut contents).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Write the state to a temporary file first so readers never see a partial state
+    pub fn write<T: Serialize>(&self, state: &T) -> Result<(), Box<dyn Error>> {
+        let tmp = self.path.with_extension("json.tmp");
+        let mut f = File::create(&tmp)?;
+        f.write_all(serde_json::to_string(state)?.as_bytes())?;
+        f.sync_all()?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_state_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateFile::new(tmp.path().join("state.json"));
+        assert_eq!(state.read::<HashMap<String, i32>>(), None);
+
+        let _lock = state.lock().unwrap();
+        assert!(tmp.path().join("state.lock").exists());
+        state.write(&HashMap::from([("a".to_owned(), 1)])).unwrap();
+        assert_eq!(state.read(), Some(HashMap::from([("a".to_owned(), 1)])));
+
+        fs::write(tmp.path().join("state.json"), "{").unwrap();
+        assert_eq!(state.read::<HashMap<String, i32>>(), None);
+    }
+}
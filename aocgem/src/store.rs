@@ -0,0 +1,169 @@
+use std::{env, error::Error, io, path::PathBuf, sync::OnceLock, time::Duration};
+
+use crate::{
+    fetch::get_cache_path,
+    leaderboard::{Identity, Leaderboard, PublicLeaderboard},
+};
+
+pub mod file;
+pub mod sqlite;
+
+pub use file::FileStore;
+pub use sqlite::SqliteStore;
+
+/// Persistent storage for cached leaderboards, public leaderboards and identities
+///
+/// Records are stored as they are given, any encryption happens before they
+/// reach the store.
+pub trait Store: Send + Sync {
+    fn load_leaderboard(
+        &self,
+        group: &str,
+        year: i32,
+    ) -> Result<Option<Leaderboard>, Box<dyn Error>>;
+    fn save_leaderboard(
+        &self,
+        leaderboard: &Leaderboard,
+        group: &str,
+        year: i32,
+    ) -> Result<(), Box<dyn Error>>;
+    /// How long ago the leaderboard was saved, `Duration::MAX` if it never was
+    fn get_age(&self, group: &str, year: i32) -> Result<Duration, Box<dyn Error>>;
+
+    /// Keep a copy of the leaderboard as it was at `at` (unix seconds)
+    fn save_snapshot(
+        &self,
+        leaderboard: &Leaderboard,
+        group: &str,
+        year: i32,
+        at: i64,
+    ) -> Result<(), Box<dyn Error>>;
+    /// When each snapshot of the leaderboard was taken, oldest first
+    fn snapshots(&self, group: &str, year: i32) -> Result<Vec<i64>, Box<dyn Error>>;
+    fn load_snapshot(
+        &self,
+        group: &str,
+        year: i32,
+        at: i64,
+    ) -> Result<Option<Leaderboard>, Box<dyn Error>>;
+
+    fn load_pub_leaderboard(
+        &self,
+        token: &str,
+    ) -> Result<Option<PublicLeaderboard>, Box<dyn Error>>;
+    fn save_pub_leaderboard(
+        &self,
+        token: &str,
+        leaderboard: &PublicLeaderboard,
+    ) -> Result<(), Box<dyn Error>>;
+    fn pub_leaderboard_exists(&self, token: &str) -> Result<bool, Box<dyn Error>>;
+    /// Find the public leaderboard for an AoC leaderboard id
+    fn find_pub_leaderboard(
+        &self,
+        group: &str,
+    ) -> Result<Option<PublicLeaderboard>, Box<dyn Error>>;
+    fn pub_leaderboards(&self) -> Result<Vec<PublicLeaderboard>, Box<dyn Error>>;
+
+    fn load_identity(&self, hash: &str) -> Result<Option<Identity>, Box<dyn Error>>;
+    fn save_identity(&self, identity: &Identity) -> Result<(), Box<dyn Error>>;
+    fn delete_identity(&self, hash: &str) -> Result<(), Box<dyn Error>>;
+    fn identities(&self) -> Result<Vec<Identity>, Box<dyn Error>>;
+
+    /// The directory to keep lock files in, shared by every process using the store
+    fn lock_dir(&self) -> PathBuf;
+}
+
+static STORE: OnceLock<Box<dyn Store>> = OnceLock::new();
+
+/// Open the store selected by the `AOCGEM_STORE` environment variable
+///
+/// `files` (the default) keeps everything as json files in the data dir, and
+/// `sqlite` or `sqlite:{path}` uses a SQLite database (defaults to
+/// `aocgem.sqlite` in the data dir).
+pub fn open_store() -> Result<Box<dyn Store>, Box<dyn Error>> {
+    let kind = env::var("AOCGEM_STORE").unwrap_or_else(|_| "files".into());
+    if kind == "files" {
+        Ok(Box::new(FileStore::new(get_cache_path())))
+    } else if kind == "sqlite" {
+        Ok(Box::new(SqliteStore::open(
+            get_cache_path().join("aocgem.sqlite"),
+        )?))
+    } else if let Some(path) = kind.strip_prefix("sqlite:") {
+        Ok(Box::new(SqliteStore::open(path)?))
+    } else {
+        Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unknown store {kind}"),
+        )))
+    }
+}
+
+/// Use `store` for the rest of the process
+///
+/// This has to be called before the store is first used.
+pub fn set_store(store: Box<dyn Store>) -> Result<(), Box<dyn Error>> {
+    STORE.set(store).map_err(|_| {
+        Box::new(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "The store has already been set",
+        )) as Box<dyn Error>
+    })
+}
+
+/// Get the store, falling back to the file store if none was set
+pub fn get_store() -> &'static dyn Store {
+    STORE
+        .get_or_init(|| Box::new(FileStore::new(get_cache_path())))
+        .as_ref()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::leaderboard::test::sample_leaderboard;
+
+    /// Run the same checks against any store
+    pub fn check_store(store: &dyn Store) {
+        assert!(store.load_leaderboard("123", 2022).unwrap().is_none());
+        assert_eq!(store.get_age("123", 2022).unwrap(), Duration::MAX);
+        store
+            .save_leaderboard(&sample_leaderboard(), "123", 2022)
+            .unwrap();
+        let leaderboard = store.load_leaderboard("123", 2022).unwrap().unwrap();
+        assert_eq!(leaderboard.owner_id, 1);
+        assert!(store.get_age("123", 2022).unwrap() < Duration::from_secs(60));
+
+        assert!(store.snapshots("123", 2022).unwrap().is_empty());
+        store
+            .save_snapshot(&sample_leaderboard(), "123", 2022, 200)
+            .unwrap();
+        store
+            .save_snapshot(&sample_leaderboard(), "123", 2022, 100)
+            .unwrap();
+        assert_eq!(store.snapshots("123", 2022).unwrap(), vec![100, 200]);
+        assert!(store.snapshots("123", 2021).unwrap().is_empty());
+        assert!(store.load_snapshot("123", 2022, 100).unwrap().is_some());
+        assert!(store.load_snapshot("123", 2022, 150).unwrap().is_none());
+
+        assert!(!store.pub_leaderboard_exists("tok").unwrap());
+        assert!(store.find_pub_leaderboard("123").unwrap().is_none());
+        let board = PublicLeaderboard::new("tok", "123", "session");
+        store.save_pub_leaderboard("tok", &board).unwrap();
+        assert!(store.pub_leaderboard_exists("tok").unwrap());
+        let found = store.find_pub_leaderboard("123").unwrap().unwrap();
+        assert_eq!(found.token, "tok");
+        let loaded = store.load_pub_leaderboard("tok").unwrap().unwrap();
+        assert_eq!(loaded.session, "session");
+        assert_eq!(store.pub_leaderboards().unwrap().len(), 1);
+
+        assert!(store.load_identity("SHA256:AB").unwrap().is_none());
+        store
+            .save_identity(&Identity::new("SHA256:AB", "session"))
+            .unwrap();
+        let identity = store.load_identity("SHA256:AB").unwrap().unwrap();
+        assert_eq!(identity.session, "session");
+        assert_eq!(store.identities().unwrap().len(), 1);
+        store.delete_identity("SHA256:AB").unwrap();
+        assert!(store.load_identity("SHA256:AB").unwrap().is_none());
+    }
+}
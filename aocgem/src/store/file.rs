@@ -0,0 +1,323 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    index::PubIndex,
+    leaderboard::{Identity, Leaderboard, PublicLeaderboard},
+};
+
+use super::Store;
+
+/// Stores everything as json files in a directory
+///
+/// * `{group}-{year}.json` cached leaderboards
+/// * `history/{group}-{year}/{time}.json` snapshots of cached leaderboards
+/// * `pub/{token}.json` public leaderboards
+/// * `identity/{hash}.json` identities
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn get_data_path(&self, group: &str, year: i32) -> PathBuf {
+        self.dir.join(format!("{group}-{year}.json"))
+    }
+
+    fn get_snapshot_dir(&self, group: &str, year: i32) -> PathBuf {
+        self.dir.join("history").join(format!("{group}-{year}"))
+    }
+
+    fn get_pub_dir(&self) -> PathBuf {
+        self.dir.join("pub")
+    }
+
+    fn get_pub_data_path(&self, token: &str) -> PathBuf {
+        self.get_pub_dir().join(format!("{token}.json"))
+    }
+
+    #[inline]
+    fn get_pub_index(&self) -> PubIndex {
+        PubIndex::new(self.get_pub_dir())
+    }
+
+    fn get_identity_dir(&self) -> PathBuf {
+        self.dir.join("identity")
+    }
+
+    fn get_identity_path(&self, hash: &str) -> PathBuf {
+        // Certificate hashes look like `SHA256:ABCD...`, keep only the safe characters
+        let name: String = hash.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        self.get_identity_dir().join(format!("{name}.json"))
+    }
+
+    /// Load the board for an index entry, if the entry is still correct
+    fn load_indexed_board(
+        &self,
+        token: &str,
+        group: &str,
+    ) -> Result<Option<PublicLeaderboard>, Box<dyn Error>> {
+        Ok(self
+            .load_pub_leaderboard(token)?
+            .filter(|pub_board| pub_board.id == group))
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut f = File::open(path)?;
+    let mut contents = String::new();
+    f.read_to_string(&mut contents)?;
+    Ok(Some(serde_json::from_str(&contents)?))
+}
+
+/// Write to a temporary file, then move it into place
+///
+/// Readers will either see the old file or the new one, never a partial write.
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Box<dyn Error>> {
+    let parent = path.parent().unwrap();
+    if !parent.exists() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension(format!("{}-{}.tmp", process::id(), rand::random::<u32>()));
+    let mut f = File::create(&tmp)?;
+    let val = serde_json::to_string(value)?;
+    if let Err(err) = f.write_all(val.as_bytes()).and_then(|_| f.sync_all()) {
+        let _ = fs::remove_file(&tmp);
+        return Err(Box::new(err));
+    }
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+fn read_dir_json<T: DeserializeOwned>(dir: &Path) -> Result<Vec<T>, Box<dyn Error>> {
+    let mut values = Vec::new();
+    if !dir.exists() {
+        return Ok(values);
+    }
+    for child in dir.read_dir()? {
+        let child = child?.path();
+        if !child.is_file() {
+            continue;
+        }
+        if child.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        if let Some(value) = read_json(&child)? {
+            values.push(value);
+        }
+    }
+    Ok(values)
+}
+
+impl Store for FileStore {
+    fn load_leaderboard(
+        &self,
+        group: &str,
+        year: i32,
+    ) -> Result<Option<Leaderboard>, Box<dyn Error>> {
+        read_json(&self.get_data_path(group, year))
+    }
+
+    fn save_leaderboard(
+        &self,
+        leaderboard: &Leaderboard,
+        group: &str,
+        year: i32,
+    ) -> Result<(), Box<dyn Error>> {
+        write_json(&self.get_data_path(group, year), leaderboard)
+    }
+
+    fn get_age(&self, group: &str, year: i32) -> Result<Duration, Box<dyn Error>> {
+        let path = self.get_data_path(group, year);
+        if !path.exists() {
+            return Ok(Duration::MAX);
+        }
+        let time = path.metadata()?.modified()?;
+        Ok(SystemTime::now().duration_since(time)?)
+    }
+
+    fn save_snapshot(
+        &self,
+        leaderboard: &Leaderboard,
+        group: &str,
+        year: i32,
+        at: i64,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = self
+            .get_snapshot_dir(group, year)
+            .join(format!("{at}.json"));
+        write_json(&path, leaderboard)
+    }
+
+    fn snapshots(&self, group: &str, year: i32) -> Result<Vec<i64>, Box<dyn Error>> {
+        let dir = self.get_snapshot_dir(group, year);
+        let mut times = Vec::new();
+        if !dir.exists() {
+            return Ok(times);
+        }
+        for child in dir.read_dir()? {
+            let child = child?.path();
+            if child.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(at) = child
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<i64>().ok())
+            {
+                times.push(at);
+            }
+        }
+        times.sort_unstable();
+        Ok(times)
+    }
+
+    fn load_snapshot(
+        &self,
+        group: &str,
+        year: i32,
+        at: i64,
+    ) -> Result<Option<Leaderboard>, Box<dyn Error>> {
+        read_json(
+            &self
+                .get_snapshot_dir(group, year)
+                .join(format!("{at}.json")),
+        )
+    }
+
+    fn load_pub_leaderboard(
+        &self,
+        token: &str,
+    ) -> Result<Option<PublicLeaderboard>, Box<dyn Error>> {
+        read_json(&self.get_pub_data_path(token))
+    }
+
+    fn save_pub_leaderboard(
+        &self,
+        token: &str,
+        leaderboard: &PublicLeaderboard,
+    ) -> Result<(), Box<dyn Error>> {
+        write_json(&self.get_pub_data_path(token), leaderboard)?;
+        self.get_pub_index().insert(&leaderboard.id, token)
+    }
+
+    fn pub_leaderboard_exists(&self, token: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(self.get_pub_data_path(token).exists())
+    }
+
+    fn find_pub_leaderboard(
+        &self,
+        group: &str,
+    ) -> Result<Option<PublicLeaderboard>, Box<dyn Error>> {
+        // Every saved board is added to the index, so a miss means there is no board
+        let index = self.get_pub_index();
+        let token = match index.get(group)? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        if let Some(pub_board) = self.load_indexed_board(&token, group)? {
+            return Ok(Some(pub_board));
+        }
+
+        // The entry is out of date, so make sure the index is correct
+        match index.rebuild()?.remove(group) {
+            Some(token) => self.load_indexed_board(&token, group),
+            None => Ok(None),
+        }
+    }
+
+    fn pub_leaderboards(&self) -> Result<Vec<PublicLeaderboard>, Box<dyn Error>> {
+        read_dir_json(&self.get_pub_dir())
+    }
+
+    fn load_identity(&self, hash: &str) -> Result<Option<Identity>, Box<dyn Error>> {
+        read_json(&self.get_identity_path(hash))
+    }
+
+    fn save_identity(&self, identity: &Identity) -> Result<(), Box<dyn Error>> {
+        write_json(&self.get_identity_path(&identity.hash), identity)
+    }
+
+    fn delete_identity(&self, hash: &str) -> Result<(), Box<dyn Error>> {
+        let path = self.get_identity_path(hash);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn identities(&self) -> Result<Vec<Identity>, Box<dyn Error>> {
+        read_dir_json(&self.get_identity_dir())
+    }
+
+    fn lock_dir(&self) -> PathBuf {
+        self.dir.join("locks")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{leaderboard::test::sample_leaderboard, store::test::check_store};
+
+    #[test]
+    fn test_file_store() {
+        let tmp = tempfile::tempdir().unwrap();
+        check_store(&FileStore::new(tmp.path()));
+    }
+
+    #[test]
+    fn test_no_temporary_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = FileStore::new(tmp.path());
+        store
+            .save_leaderboard(&sample_leaderboard(), "123", 2022)
+            .unwrap();
+        store
+            .save_leaderboard(&sample_leaderboard(), "123", 2022)
+            .unwrap();
+
+        let files: Vec<_> = tmp.path().read_dir().unwrap().collect();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn test_find_uses_index() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = FileStore::new(tmp.path());
+        let board = PublicLeaderboard::new("abc", "123", "session");
+        store.save_pub_leaderboard("abc", &board).unwrap();
+
+        // Boards that were never indexed aren't found by scanning
+        let unindexed = PublicLeaderboard::new("def", "456", "session");
+        write_json(&store.get_pub_data_path("def"), &unindexed).unwrap();
+        assert!(store.find_pub_leaderboard("456").unwrap().is_none());
+
+        // An entry for a board that has changed ids rebuilds the index
+        let moved = PublicLeaderboard::new("abc", "789", "session");
+        write_json(&store.get_pub_data_path("abc"), &moved).unwrap();
+        assert!(store.find_pub_leaderboard("123").unwrap().is_none());
+        assert_eq!(
+            store.find_pub_leaderboard("456").unwrap().unwrap().token,
+            "def"
+        );
+        assert_eq!(
+            store.find_pub_leaderboard("789").unwrap().unwrap().token,
+            "abc"
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::leaderboard::{Identity, Leaderboard, PublicLeaderboard};
+
+use super::Store;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS leaderboard (
+    grp TEXT NOT NULL,
+    year INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    saved_at INTEGER NOT NULL,
+    PRIMARY KEY (grp, year)
+);
+CREATE TABLE IF NOT EXISTS snapshot (
+    grp TEXT NOT NULL,
+    year INTEGER NOT NULL,
+    taken_at INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (grp, year, taken_at)
+);
+CREATE TABLE IF NOT EXISTS pub_leaderboard (
+    token TEXT PRIMARY KEY,
+    id TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS pub_leaderboard_id ON pub_leaderboard (id);
+CREATE TABLE IF NOT EXISTS identity (
+    hash TEXT PRIMARY KEY,
+    data TEXT NOT NULL
+);
+";
+
+/// Stores everything in a SQLite database
+///
+/// Records are kept as json so that new fields don't need a migration.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    lock_dir: PathBuf,
+}
+
+impl SqliteStore {
+    fn from_connection(conn: Connection, lock_dir: PathBuf) -> Result<Self, Box<dyn Error>> {
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            lock_dir,
+        })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Self::from_connection(Connection::open(path)?, path.with_extension("locks"))
+    }
+
+    /// A store that only lives as long as the process, useful for tests
+    pub fn in_memory() -> Result<Self, Box<dyn Error>> {
+        let lock_dir = env::temp_dir().join(format!("aocgem-{}.locks", process::id()));
+        Self::from_connection(Connection::open_in_memory()?, lock_dir)
+    }
+
+    fn query_json<T: serde::de::DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+        let data: Option<String> = conn.query_row(sql, params, |row| row.get(0)).optional()?;
+        Ok(match data {
+            Some(data) => Some(serde_json::from_str(&data)?),
+            None => None,
+        })
+    }
+
+    fn query_all_json<T: serde::de::DeserializeOwned>(
+        &self,
+        sql: &str,
+    ) -> Result<Vec<T>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(sql)?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+        let mut values = Vec::new();
+        for data in rows {
+            values.push(serde_json::from_str(&data?)?);
+        }
+        Ok(values)
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+impl Store for SqliteStore {
+    fn load_leaderboard(
+        &self,
+        group: &str,
+        year: i32,
+    ) -> Result<Option<Leaderboard>, Box<dyn Error>> {
+        self.query_json(
+            "SELECT data FROM leaderboard WHERE grp = ?1 AND year = ?2",
+            params![group, year],
+        )
+    }
+
+    fn save_leaderboard(
+        &self,
+        leaderboard: &Leaderboard,
+        group: &str,
+        year: i32,
+    ) -> Result<(), Box<dyn Error>> {
+        let data = serde_json::to_string(leaderboard)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO leaderboard (grp, year, data, saved_at) VALUES (?1, ?2, ?3, ?4)",
+            params![group, year, data, unix_now()],
+        )?;
+        Ok(())
+    }
+
+    fn get_age(&self, group: &str, year: i32) -> Result<Duration, Box<dyn Error>> {
+        let saved_at: Option<i64> = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT saved_at FROM leaderboard WHERE grp = ?1 AND year = ?2",
+                params![group, year],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(match saved_at {
+            Some(saved_at) => Duration::from_secs((unix_now() - saved_at).max(0) as u64),
+            None => Duration::MAX,
+        })
+    }
+
+    fn save_snapshot(
+        &self,
+        leaderboard: &Leaderboard,
+        group: &str,
+        year: i32,
+        at: i64,
+    ) -> Result<(), Box<dyn Error>> {
+        let data = serde_json::to_string(leaderboard)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO snapshot (grp, year, taken_at, data) VALUES (?1, ?2, ?3, ?4)",
+            params![group, year, at, data],
+        )?;
+        Ok(())
+    }
+
+    fn snapshots(&self, group: &str, year: i32) -> Result<Vec<i64>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT taken_at FROM snapshot WHERE grp = ?1 AND year = ?2 ORDER BY taken_at",
+        )?;
+        let rows = statement.query_map(params![group, year], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn load_snapshot(
+        &self,
+        group: &str,
+        year: i32,
+        at: i64,
+    ) -> Result<Option<Leaderboard>, Box<dyn Error>> {
+        self.query_json(
+            "SELECT data FROM snapshot WHERE grp = ?1 AND year = ?2 AND taken_at = ?3",
+            params![group, year, at],
+        )
+    }
+
+    fn load_pub_leaderboard(
+        &self,
+        token: &str,
+    ) -> Result<Option<PublicLeaderboard>, Box<dyn Error>> {
+        self.query_json(
+            "SELECT data FROM pub_leaderboard WHERE token = ?1",
+            params![token],
+        )
+    }
+
+    fn save_pub_leaderboard(
+        &self,
+        token: &str,
+        leaderboard: &PublicLeaderboard,
+    ) -> Result<(), Box<dyn Error>> {
+        let data = serde_json::to_string(leaderboard)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO pub_leaderboard (token, id, data) VALUES (?1, ?2, ?3)",
+            params![token, leaderboard.id, data],
+        )?;
+        Ok(())
+    }
+
+    fn pub_leaderboard_exists(&self, token: &str) -> Result<bool, Box<dyn Error>> {
+        let count: i64 = self.conn.lock().unwrap().query_row(
+            "SELECT COUNT(*) FROM pub_leaderboard WHERE token = ?1",
+            params![token],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    fn find_pub_leaderboard(
+        &self,
+        group: &str,
+    ) -> Result<Option<PublicLeaderboard>, Box<dyn Error>> {
+        self.query_json(
+            "SELECT data FROM pub_leaderboard WHERE id = ?1 LIMIT 1",
+            params![group],
+        )
+    }
+
+    fn pub_leaderboards(&self) -> Result<Vec<PublicLeaderboard>, Box<dyn Error>> {
+        self.query_all_json("SELECT data FROM pub_leaderboard")
+    }
+
+    fn load_identity(&self, hash: &str) -> Result<Option<Identity>, Box<dyn Error>> {
+        self.query_json("SELECT data FROM identity WHERE hash = ?1", params![hash])
+    }
+
+    fn save_identity(&self, identity: &Identity) -> Result<(), Box<dyn Error>> {
+        let data = serde_json::to_string(identity)?;
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO identity (hash, data) VALUES (?1, ?2)",
+            params![identity.hash, data],
+        )?;
+        Ok(())
+    }
+
+    fn delete_identity(&self, hash: &str) -> Result<(), Box<dyn Error>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM identity WHERE hash = ?1", params![hash])?;
+        Ok(())
+    }
+
+    fn identities(&self) -> Result<Vec<Identity>, Box<dyn Error>> {
+        self.query_all_json("SELECT data FROM identity")
+    }
+
+    fn lock_dir(&self) -> PathBuf {
+        self.lock_dir.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::test::check_store;
+
+    #[test]
+    fn test_sqlite_store() {
+        check_store(&SqliteStore::in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("aocgem.sqlite");
+        check_store(&SqliteStore::open(&path).unwrap());
+
+        // The data should still be there after reopening
+        let store = SqliteStore::open(&path).unwrap();
+        assert!(store.pub_leaderboard_exists("tok").unwrap());
+    }
+}